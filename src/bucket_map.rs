@@ -0,0 +1,254 @@
+use std::borrow::Borrow;
+use std::hash::{BuildHasher, Hasher};
+
+/// A hash map with a fixed number of buckets, each holding its entries in the order they
+/// were inserted. Keys are hashed from their bytes, so any `Hasher` can pick the bucket.
+pub struct BucketMap<K, V, S> {
+    /// Each entry carries a stamp recording when its key was first inserted.
+    buckets: Vec<Vec<(u64, K, V)>>,
+    hash_builder: S,
+    next_stamp: u64,
+    len: usize,
+}
+
+impl<K: AsRef<[u8]> + Eq, V, S: BuildHasher> BucketMap<K, V, S> {
+    pub fn with_hasher(bucket_count: usize, hash_builder: S) -> BucketMap<K, V, S> {
+        assert!(bucket_count > 0, "A BucketMap needs at least one bucket.");
+        BucketMap {
+            buckets: (0..bucket_count).map(|_| Vec::new()).collect(),
+            hash_builder,
+            next_stamp: 0,
+            len: 0,
+        }
+    }
+
+    pub fn bucket_index<Q: AsRef<[u8]> + ?Sized>(&self, key: &Q) -> usize {
+        let mut hasher = self.hash_builder.build_hasher();
+        hasher.write(key.as_ref());
+        (hasher.finish() % self.buckets.len() as u64) as usize
+    }
+
+    fn position<Q>(&self, bucket: usize, key: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Eq + ?Sized,
+    {
+        self.buckets[bucket]
+            .iter()
+            .position(|(_, other_key, _)| other_key.borrow() == key)
+    }
+
+    /// Inserts or replaces the value for `key`. A replaced entry keeps its place in the
+    /// bucket and the previous value is returned.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        match self.entry(key) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value)),
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+                None
+            }
+        }
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<[u8]> + Eq + ?Sized,
+    {
+        let bucket = self.bucket_index(key);
+        self.position(bucket, key)
+            .map(|index| &self.buckets[bucket][index].2)
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<[u8]> + Eq + ?Sized,
+    {
+        let bucket = self.bucket_index(key);
+        self.position(bucket, key)
+            .map(|index| &mut self.buckets[bucket][index].2)
+    }
+
+    /// Removes `key`, shifting later entries in its bucket forward.
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+    where
+        K: Borrow<Q>,
+        Q: AsRef<[u8]> + Eq + ?Sized,
+    {
+        let bucket = self.bucket_index(key);
+        let index = self.position(bucket, key)?;
+        self.len -= 1;
+        Some(self.buckets[bucket].remove(index).2)
+    }
+
+    pub fn entry(&mut self, key: K) -> Entry<'_, K, V> {
+        let bucket = self.bucket_index(&key);
+        match self.position(bucket, &key) {
+            Some(index) => Entry::Occupied(OccupiedEntry {
+                bucket: &mut self.buckets[bucket],
+                index,
+            }),
+            None => Entry::Vacant(VacantEntry {
+                bucket: &mut self.buckets[bucket],
+                next_stamp: &mut self.next_stamp,
+                len: &mut self.len,
+                key,
+            }),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The entries of each bucket, in bucket order.
+    pub fn buckets(&self) -> impl Iterator<Item = impl Iterator<Item = (&K, &V)>> {
+        self.buckets
+            .iter()
+            .map(|bucket| bucket.iter().map(|(_, key, value)| (key, value)))
+    }
+
+    /// All entries in the order their keys were first inserted.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        let mut entries: Vec<_> = self.buckets.iter().flatten().collect();
+        entries.sort_by_key(|(stamp, _, _)| *stamp);
+        entries.into_iter().map(|(_, key, value)| (key, value))
+    }
+}
+
+pub enum Entry<'a, K, V> {
+    Occupied(OccupiedEntry<'a, K, V>),
+    Vacant(VacantEntry<'a, K, V>),
+}
+
+impl<'a, K, V> Entry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => &entry.key,
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with(self, default: impl FnOnce() -> V) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn and_modify(mut self, modify: impl FnOnce(&mut V)) -> Entry<'a, K, V> {
+        if let Entry::Occupied(entry) = &mut self {
+            modify(entry.get_mut());
+        }
+        self
+    }
+}
+
+pub struct OccupiedEntry<'a, K, V> {
+    bucket: &'a mut Vec<(u64, K, V)>,
+    index: usize,
+}
+
+impl<'a, K, V> OccupiedEntry<'a, K, V> {
+    pub fn key(&self) -> &K {
+        &self.bucket[self.index].1
+    }
+
+    pub fn get(&self) -> &V {
+        &self.bucket[self.index].2
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        &mut self.bucket[self.index].2
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        &mut self.bucket[self.index].2
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+}
+
+pub struct VacantEntry<'a, K, V> {
+    bucket: &'a mut Vec<(u64, K, V)>,
+    next_stamp: &'a mut u64,
+    len: &'a mut usize,
+    key: K,
+}
+
+impl<'a, K, V> VacantEntry<'a, K, V> {
+    pub fn insert(self, value: V) -> &'a mut V {
+        self.bucket.push((*self.next_stamp, self.key, value));
+        *self.next_stamp += 1;
+        *self.len += 1;
+        &mut self
+            .bucket
+            .last_mut()
+            .expect("Bucket should hold the new entry.")
+            .2
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucket_map::{BucketMap, Entry};
+    use crate::fnv1::BuildFnv1Hasher;
+
+    #[test]
+    pub fn insert_get_remove() {
+        let mut map: BucketMap<String, u32, _> = BucketMap::with_hasher(4, BuildFnv1Hasher);
+        assert_eq!(map.insert("a".to_owned(), 1), None);
+        assert_eq!(map.insert("b".to_owned(), 2), None);
+        assert_eq!(map.insert("a".to_owned(), 3), Some(1));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.get("a"), Some(&3));
+        assert_eq!(map.get("c"), None);
+
+        *map.get_mut("b").expect("Key should exist.") += 10;
+        assert_eq!(map.remove("b"), Some(12));
+        assert_eq!(map.remove("b"), None);
+        assert_eq!(map.len(), 1);
+        assert!(!map.is_empty());
+    }
+
+    #[test]
+    pub fn insertion_order() {
+        let mut map: BucketMap<&str, u32, _> = BucketMap::with_hasher(3, BuildFnv1Hasher);
+        for (index, key) in ["x", "y", "z", "w"].into_iter().enumerate() {
+            map.insert(key, index as u32);
+        }
+        map.insert("y", 10);
+        map.remove("x");
+        map.insert("x", 20);
+
+        let keys: Vec<_> = map.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, vec!["y", "z", "w", "x"]);
+        assert_eq!(map.buckets().map(|bucket| bucket.count()).sum::<usize>(), 4);
+    }
+
+    #[test]
+    pub fn entry() {
+        let mut map: BucketMap<String, u32, _> = BucketMap::with_hasher(1, BuildFnv1Hasher);
+        *map.entry("a".to_owned()).or_insert(0) += 1;
+        *map.entry("a".to_owned()).or_insert(0) += 1;
+        map.entry("b".to_owned())
+            .and_modify(|value| *value += 100)
+            .or_insert_with(|| 5);
+        assert!(matches!(map.entry("a".to_owned()), Entry::Occupied(_)));
+        assert_eq!(map.entry("c".to_owned()).key(), "c");
+        assert_eq!(map.get("a"), Some(&2));
+        assert_eq!(map.get("b"), Some(&5));
+        assert_eq!(map.len(), 2);
+    }
+}
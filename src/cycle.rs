@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// States from `offset` onwards repeat every `length` steps.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Cycle {
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl Cycle {
+    /// The earliest step with the same state as step `target`.
+    pub fn equivalent_step(&self, target: usize) -> usize {
+        if target < self.offset {
+            target
+        } else {
+            self.offset + (target - self.offset) % self.length
+        }
+    }
+}
+
+/// The state after `target` steps, and the cycle used to skip ahead to it. The cycle is
+/// `None` when the target was reached before the states started repeating.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CycleResult<T> {
+    pub state: T,
+    pub cycle: Option<Cycle>,
+}
+
+fn advance<T>(mut state: T, step: &mut impl FnMut(&T) -> T, count: usize) -> T {
+    for _ in 0..count {
+        state = step(&state);
+    }
+    state
+}
+
+/// Floyd's tortoise and hare. Keeps two states at a time, but takes about three steps for
+/// every state before the cycle is found.
+pub fn floyd<T: Clone + PartialEq>(
+    initial: T,
+    mut step: impl FnMut(&T) -> T,
+    target: usize,
+) -> CycleResult<T> {
+    if target <= 2 {
+        return CycleResult {
+            state: advance(initial, &mut step, target),
+            cycle: None,
+        };
+    }
+
+    let mut tortoise = step(&initial);
+    let mut hare = step(&tortoise);
+    let mut hare_steps = 2;
+
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        for _ in 0..2 {
+            hare = step(&hare);
+            hare_steps += 1;
+            if hare_steps == target {
+                return CycleResult {
+                    state: hare,
+                    cycle: None,
+                };
+            }
+        }
+    }
+
+    // The hare is now a whole number of cycles ahead, so stepping both from the start
+    // meets at the first repeated state:
+    let mut offset = 0;
+    let mut tortoise = initial;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        offset += 1;
+    }
+
+    let mut length = 1;
+    hare = step(&tortoise);
+    while tortoise != hare {
+        hare = step(&hare);
+        length += 1;
+    }
+
+    let cycle = Cycle { offset, length };
+    CycleResult {
+        state: advance(tortoise, &mut step, cycle.equivalent_step(target) - offset),
+        cycle: Some(cycle),
+    }
+}
+
+/// Brent's algorithm. Keeps two states at a time and usually takes fewer steps than
+/// `floyd`.
+pub fn brent<T: Clone + PartialEq>(
+    initial: T,
+    mut step: impl FnMut(&T) -> T,
+    target: usize,
+) -> CycleResult<T> {
+    if target == 0 {
+        return CycleResult {
+            state: initial,
+            cycle: None,
+        };
+    }
+
+    // Find the length by letting the hare run for doubling distances from the tortoise:
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = initial.clone();
+    let mut hare = step(&initial);
+    let mut hare_steps = 1;
+    while tortoise != hare {
+        if hare_steps == target {
+            return CycleResult {
+                state: hare,
+                cycle: None,
+            };
+        }
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        hare_steps += 1;
+        length += 1;
+    }
+
+    // With the hare a cycle ahead of the tortoise, they meet at the first repeated state:
+    let mut offset = 0;
+    let mut tortoise = initial.clone();
+    let mut hare = advance(initial, &mut step, length);
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        offset += 1;
+    }
+
+    let cycle = Cycle { offset, length };
+    CycleResult {
+        state: advance(tortoise, &mut step, cycle.equivalent_step(target) - offset),
+        cycle: Some(cycle),
+    }
+}
+
+/// Remembers every state until one repeats, so each state is only stepped once.
+pub fn hash_indexed<T: Clone + Eq + Hash>(
+    initial: T,
+    mut step: impl FnMut(&T) -> T,
+    target: usize,
+) -> CycleResult<T> {
+    let mut seen: HashMap<T, usize> = HashMap::new();
+    let mut states: Vec<T> = Vec::new();
+    let mut state = initial;
+
+    for steps in 0..target {
+        if let Some(&offset) = seen.get(&state) {
+            let cycle = Cycle {
+                offset,
+                length: steps - offset,
+            };
+            return CycleResult {
+                state: states.swap_remove(cycle.equivalent_step(target)),
+                cycle: Some(cycle),
+            };
+        }
+
+        let next = step(&state);
+        seen.insert(state.clone(), steps);
+        states.push(state);
+        state = next;
+    }
+
+    CycleResult { state, cycle: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cycle::{brent, floyd, hash_indexed, Cycle, CycleResult};
+
+    // 0, 1, 2, ... 9, then 10 to 16 repeating:
+    fn step(state: &u32) -> u32 {
+        if *state == 16 {
+            10
+        } else {
+            state + 1
+        }
+    }
+
+    #[test]
+    pub fn finds_cycle() {
+        let expected = CycleResult {
+            state: 13,
+            cycle: Some(Cycle {
+                offset: 10,
+                length: 7,
+            }),
+        };
+        assert_eq!(floyd(0, step, 1_000_000_000), expected);
+        assert_eq!(brent(0, step, 1_000_000_000), expected);
+        assert_eq!(hash_indexed(0, step, 1_000_000_000), expected);
+    }
+
+    #[test]
+    pub fn matches_stepping() {
+        for start in [0, 9, 10, 13] {
+            let mut state = start;
+            for target in 0..60 {
+                assert_eq!(floyd(start, step, target).state, state);
+                assert_eq!(brent(start, step, target).state, state);
+                assert_eq!(hash_indexed(start, step, target).state, state);
+                state = step(&state);
+            }
+        }
+
+        assert_eq!(
+            hash_indexed(0, step, 5),
+            CycleResult {
+                state: 5,
+                cycle: None
+            }
+        );
+        assert_eq!(brent(0, |state| state + 1, 100).state, 100);
+        assert_eq!(floyd(0, |state| state + 1, 100).state, 100);
+    }
+}
@@ -79,7 +79,7 @@ impl Schematic {
         for offset in offsets {
             let coord_checking = (coord.0 + offset.0, coord.1 + offset.1);
             if let Some(SchematicItem::PartNumber { num, .. }) = self.map.get(&coord_checking) {
-                if numbers_seen.get(num).is_none() {
+                if !numbers_seen.contains(num) {
                     result.push(*num);
                     numbers_seen.insert(*num);
                 }
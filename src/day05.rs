@@ -1,230 +1,223 @@
-use std::{collections::HashSet, hash::Hash};
+use crate::interval::{Interval, IntervalSet, PiecewiseMap};
 
-#[derive(Debug)]
-struct Mapping {
-    destination_range_start: i64,
-    source_range_start: i64,
-    range_length: i64,
+/// Parses a line like `50 98 2` into the source interval and the offset added to it.
+fn parse_mapping(input: &str) -> (Interval, i64) {
+    let parsed: Vec<i64> = input
+        .split(' ')
+        .map(|item| {
+            item.parse()
+                .unwrap_or_else(|_| panic!("Mapping numbers must be valid, got: {:?}", item))
+        })
+        .collect();
+    let (destination_range_start, source_range_start, range_length) =
+        (parsed[0], parsed[1], parsed[2]);
+
+    (
+        Interval::from_length(source_range_start, range_length),
+        destination_range_start - source_range_start,
+    )
+}
+
+/// Parses a map like `seed-to-soil map:` followed by one mapping per line.
+fn parse_mapping_group(input: &str) -> PiecewiseMap {
+    PiecewiseMap::from_segments(input.split('\n').skip(1).map(parse_mapping))
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Range {
-    range_start: i64,
-    range_length: i64,
+pub struct Almanac {
+    seeds: Vec<i64>,
+    /// From seed-to-soil through to humidity-to-location.
+    maps: Vec<PiecewiseMap>,
 }
 
-impl Mapping {
-    fn new(input: &str) -> Mapping {
-        let parsed: Vec<i64> = input
+impl Almanac {
+    pub fn parse(input: &str) -> Almanac {
+        let mut chunks = input.split("\n\n");
+        let seeds: Vec<i64> = chunks
+            .next()
+            .expect("Seed chunk must exist.")
+            .replace("seeds: ", "")
             .split(' ')
-            .map(|item| {
-                item.parse()
-                    .unwrap_or_else(|_| panic!("Mapping numbers must be valid, got: {:?}", item))
-            })
+            .map(|item| item.parse().expect("Seed number must be valid."))
             .collect();
-        Mapping {
-            destination_range_start: parsed[0],
-            source_range_start: parsed[1],
-            range_length: parsed[2],
-        }
-    }
 
-    fn src_to_dst(&self, source: i64) -> Option<i64> {
-        if source >= self.source_range_start
-            && source <= (self.source_range_start + self.range_length)
-        {
-            let offset = source - self.source_range_start;
-            Some(self.destination_range_start + offset)
-        } else {
-            None
+        Almanac {
+            seeds,
+            maps: chunks.map(parse_mapping_group).collect(),
         }
     }
 
-    fn clamp(&self, source: Range) -> (Option<Range>, Option<Range>, Option<Range>) {
-        if source.range_start + source.range_length < self.source_range_start {
-            return (Some(source), None, None);
-        }
-
-        if source.range_start > self.source_range_start + self.range_length {
-            return (None, None, Some(source));
-        }
+    pub fn seeds(&self) -> &[i64] {
+        &self.seeds
+    }
 
-        let mut body = source.clone();
-        let mut head: Option<Range> = None;
-        if body.range_start < self.source_range_start {
-            let diff = self.source_range_start - body.range_start;
-            body.range_start += diff;
-            body.range_length -= diff;
-            if diff > 1 {
-                head = Some(Range {
-                    range_start: source.range_start,
-                    range_length: diff - 1,
-                });
-            }
-        }
+    /// The seeds read as pairs of range start and range length.
+    pub fn seed_ranges(&self) -> IntervalSet {
+        self.seeds
+            .chunks(2)
+            .map(|pair| Interval::from_length(pair[0], pair[1]))
+            .collect()
+    }
 
-        let source_end = source.range_start + source.range_length;
-        let mapping_end = self.source_range_start + self.range_length;
-        let mut tail: Option<Range> = None;
-        if source_end > mapping_end {
-            let diff = source_end - mapping_end;
-            body.range_length -= diff;
-            if diff > 1 {
-                tail = Some(Range {
-                    range_start: mapping_end + 1,
-                    range_length: diff - 1,
-                });
-            }
-        }
+    /// Every map in the almanac composed into one.
+    pub fn seed_to_location(&self) -> PiecewiseMap {
+        self.maps
+            .iter()
+            .fold(PiecewiseMap::new(), |composed, map| composed.then(map))
+    }
 
-        let offset = body.range_start - self.source_range_start;
-        body.range_start = self.destination_range_start + offset;
+    /// The seed-to-location map from its first segment to its last, including the gaps
+    /// between segments where seeds keep their number. Seeds outside the table do too.
+    pub fn table(&self) -> Vec<TableRow> {
+        let seed_to_location = self.seed_to_location();
+        let segments = seed_to_location.segments();
+        let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+            return Vec::new();
+        };
+
+        seed_to_location
+            .pieces(Interval::new(first.0.start, last.0.end))
+            .into_iter()
+            .map(|(source, offset)| TableRow {
+                source,
+                destination: source.shift(offset),
+                offset,
+            })
+            .collect()
+    }
 
-        (head, Some(body), tail)
+    /// Every seed number planted at `location`, whether or not the almanac lists it.
+    pub fn seeds_for_location(&self, location: i64) -> IntervalSet {
+        let location: IntervalSet =
+            std::iter::once(Interval::new(location, location + 1)).collect();
+        self.seed_to_location().preimage(&location)
     }
 }
 
-#[derive(Debug)]
-struct MappingGroup {
-    mappings: Vec<Mapping>,
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TableFormat {
+    Table,
+    Csv,
 }
 
-impl MappingGroup {
-    fn new(input: &str) -> MappingGroup {
-        MappingGroup {
-            mappings: input.split('\n').skip(1).map(Mapping::new).collect(),
-        }
-    }
-
-    fn src_to_dst(&self, source: i64) -> i64 {
-        for mapping in self.mappings.iter() {
-            if let Some(dst) = mapping.src_to_dst(source) {
-                return dst;
-            }
-        }
+/// A range of seeds and the locations they are planted at.
+#[derive(PartialEq, Eq, Debug)]
+pub struct TableRow {
+    pub source: Interval,
+    pub destination: Interval,
+    pub offset: i64,
+}
 
-        source
-    }
+/// Formats the rows with half-open intervals, like `[50, 98)`.
+pub fn format_table(rows: &[TableRow], format: TableFormat) -> String {
+    let mut result = String::new();
 
-    fn clamp(&self, ranges: Vec<Range>) -> Vec<Range> {
-        let mut result: HashSet<Range> = HashSet::new();
-        let mut to_check: HashSet<Range> = HashSet::new();
-
-        for range in ranges {
-            for mapping in self.mappings.iter() {
-                let (head, body, tail) = mapping.clamp(range.clone());
-                if let Some(head) = head {
-                    to_check.insert(head);
-                }
-                if let Some(body) = body {
-                    result.insert(body);
-                }
-                if let Some(tail) = tail {
-                    to_check.insert(tail);
-                }
-            }
-        }
-
-        let mut seen = to_check.clone();
-        while !to_check.is_empty() {
-            let range = to_check
+    match format {
+        TableFormat::Table => {
+            let cells: Vec<_> = rows
                 .iter()
-                .next()
-                .expect("Set should have at least 1 element.")
-                .clone();
-            let mut found_match = false;
-            let mut pieces: Vec<Range> = Vec::new();
-
-            for mapping in self.mappings.iter() {
-                let (head, body, tail) = mapping.clamp(range.clone());
-                if body.is_some() {
-                    found_match = true;
-                    // No need to push body, it should've been covered already by our earlier loop.
-
-                    if let Some(head) = head {
-                        pieces.push(head);
-                    }
-                    if let Some(tail) = tail {
-                        pieces.push(tail);
-                    }
-                }
+                .map(|row| {
+                    (
+                        format!("[{}, {})", row.source.start, row.source.end),
+                        format!("[{}, {})", row.destination.start, row.destination.end),
+                        row.offset,
+                    )
+                })
+                .collect();
+            let width = cells
+                .iter()
+                .map(|(source, destination, _)| source.len().max(destination.len()))
+                .max()
+                .unwrap_or(0)
+                .max(11);
+            result += &format!(
+                "| {:<width$} | {:<width$} | {:>12} |\n",
+                "Seeds", "Locations", "Offset"
+            );
+            for (source, destination, offset) in cells {
+                result += &format!(
+                    "| {:<width$} | {:<width$} | {:>12} |\n",
+                    source, destination, offset
+                );
             }
-
-            if found_match {
-                for range in pieces {
-                    if !seen.contains(&range) {
-                        to_check.insert(range.clone());
-                        seen.insert(range);
-                    }
-                }
-            } else {
-                result.insert(range.clone());
+        }
+        TableFormat::Csv => {
+            result += "source_start,source_end,destination_start,destination_end,offset\n";
+            for row in rows {
+                result += &format!(
+                    "{},{},{},{},{}\n",
+                    row.source.start,
+                    row.source.end,
+                    row.destination.start,
+                    row.destination.end,
+                    row.offset
+                );
             }
-            to_check.remove(&range);
         }
-
-        result.into_iter().collect()
     }
+
+    result
 }
 
 pub fn day05_part_1(input: &str) -> i64 {
-    let mut lowest_location = i64::MAX;
-    let mut chunks = input.split("\n\n");
-    let seeds: Vec<i64> = chunks
-        .next()
-        .expect("Seed chunk must exist.")
-        .replace("seeds: ", "")
-        .split(' ')
-        .map(|item| item.parse().expect("Seed number must be valid."))
-        .collect();
-
-    let mappings: Vec<_> = chunks.map(MappingGroup::new).collect();
-
-    for seed in seeds.iter() {
-        let location = mappings
-            .iter()
-            .fold(*seed, |last, mapping| mapping.src_to_dst(last));
-
-        if location < lowest_location {
-            lowest_location = location;
-        }
-    }
+    let almanac = Almanac::parse(input);
+    let seed_to_location = almanac.seed_to_location();
 
-    lowest_location
+    almanac
+        .seeds()
+        .iter()
+        .map(|&seed| seed_to_location.apply(seed))
+        .min()
+        .expect("Expected to find a lowest value.")
 }
 
 pub fn day05_part_2(input: &str) -> i64 {
-    let mut chunks = input.split("\n\n");
-    let mut seeds: Vec<Range> = Vec::new();
-    for pair in chunks
-        .next()
-        .expect("Seed chunk must exist.")
-        .replace("seeds: ", "")
-        .split(' ')
-        .map(|item| item.parse::<i64>().expect("Seed number must be valid."))
-        .collect::<Vec<_>>()
-        .chunks(2)
-    {
-        seeds.push(Range {
-            range_start: pair[0],
-            range_length: pair[1],
-        });
-    }
-
-    let mappings: Vec<_> = chunks.map(MappingGroup::new).collect();
+    let almanac = Almanac::parse(input);
 
-    mappings
-        .iter()
-        .fold(seeds, |last, mapping| mapping.clamp(last))
-        .iter()
-        .min_by(|x, y| x.range_start.cmp(&y.range_start))
+    almanac
+        .seed_to_location()
+        .image(&almanac.seed_ranges())
+        .min()
         .expect("Expected to find a lowest value.")
-        .range_start
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day05::{day05_part_1, day05_part_2};
+    use crate::day05::{day05_part_1, day05_part_2, format_table, Almanac, TableFormat, TableRow};
+    use crate::interval::{Interval, IntervalSet};
+
+    const EXAMPLE: &str = "seeds: 79 14 55 13
+
+seed-to-soil map:
+50 98 2
+52 50 48
+
+soil-to-fertilizer map:
+0 15 37
+37 52 2
+39 0 15
+
+fertilizer-to-water map:
+49 53 8
+0 11 42
+42 0 7
+57 7 4
+
+water-to-light map:
+88 18 7
+18 25 70
+
+light-to-temperature map:
+45 77 23
+81 45 19
+68 64 13
+
+temperature-to-humidity map:
+0 69 1
+1 0 69
+
+humidity-to-location map:
+60 56 37
+56 93 4";
 
     #[test]
     pub fn part1_example() {
@@ -309,4 +302,90 @@ humidity-to-location map:
             46
         );
     }
+
+    #[test]
+    pub fn composed_ranges() {
+        let almanac = Almanac::parse(EXAMPLE);
+        let seed_to_location = almanac.seed_to_location();
+        assert_eq!(
+            almanac
+                .seeds()
+                .iter()
+                .map(|&seed| seed_to_location.apply(seed))
+                .collect::<Vec<_>>(),
+            vec![82, 43, 86, 35]
+        );
+
+        // The composed map agrees with applying each map in turn:
+        let single: IntervalSet = [Interval::new(82, 83)].into_iter().collect();
+        assert_eq!(
+            seed_to_location.image(&single).intervals(),
+            [Interval::new(46, 47)]
+        );
+        let everything: IntervalSet = [Interval::new(0, 100)].into_iter().collect();
+        let image = seed_to_location.image(&everything);
+        assert_eq!(image.len(), 100);
+        for seed in 0..100 {
+            assert!(image.contains(seed_to_location.apply(seed)));
+        }
+    }
+
+    #[test]
+    pub fn table() {
+        let almanac = Almanac::parse(EXAMPLE);
+        let rows = almanac.table();
+        assert_eq!(
+            rows[0],
+            TableRow {
+                source: Interval::new(0, 14),
+                destination: Interval::new(22, 36),
+                offset: 22,
+            }
+        );
+        assert!(rows
+            .windows(2)
+            .all(|pair| pair[0].source.end == pair[1].source.start));
+        for row in rows.iter() {
+            let seeds = [row.source.start, row.source.end - 1];
+            for seed in seeds {
+                assert_eq!(almanac.seed_to_location().apply(seed), seed + row.offset);
+            }
+        }
+
+        let csv = format_table(&rows[..1], TableFormat::Csv);
+        assert_eq!(
+            csv,
+            "source_start,source_end,destination_start,destination_end,offset
+0,14,22,36,22
+"
+        );
+        assert_eq!(
+            format_table(&rows[..1], TableFormat::Table),
+            "| Seeds       | Locations   |       Offset |
+| [0, 14)     | [22, 36)    |           22 |
+"
+        );
+    }
+
+    #[test]
+    pub fn reverse_lookup() {
+        let almanac = Almanac::parse(EXAMPLE);
+        let seeds = almanac.seeds_for_location(46);
+        assert!(seeds.contains(82));
+        for interval in seeds.intervals() {
+            assert_eq!(almanac.seed_to_location().apply(interval.start), 46);
+        }
+        assert_eq!(
+            seeds.intersection(&almanac.seed_ranges()).intervals(),
+            [Interval::new(82, 83)]
+        );
+
+        // Nothing in the part 2 seed ranges reaches a lower location:
+        for location in 0..46 {
+            assert!(almanac
+                .seeds_for_location(location)
+                .intersection(&almanac.seed_ranges())
+                .is_empty());
+        }
+    }
 }
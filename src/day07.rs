@@ -1,64 +1,164 @@
-use std::{cmp::Ordering, collections::HashMap};
-
-#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Clone, Copy)]
-enum CardKind {
-    A,
-    K,
-    Q,
-    J,
-    T,
-    Nine,
-    Eight,
-    Seven,
-    Six,
-    Five,
-    Four,
-    Three,
-    Two,
-    One,
+use std::collections::HashMap;
+
+/// How two hands of the same kind are ordered.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum TieBreak {
+    /// Compare cards one at a time in the order they were dealt.
+    CardOrder,
+    /// Compare cards after grouping them, largest group first, as in poker.
+    SortedHand,
 }
 
-impl CardKind {
-    const VALUES: [CardKind; 14] = [
-        CardKind::A,
-        CardKind::K,
-        CardKind::Q,
-        CardKind::J,
-        CardKind::T,
-        CardKind::Nine,
-        CardKind::Eight,
-        CardKind::Seven,
-        CardKind::Six,
-        CardKind::Five,
-        CardKind::Four,
-        CardKind::Three,
-        CardKind::Two,
-        CardKind::One,
-    ];
-
-    fn from(input: char) -> CardKind {
-        match input {
-            'A' => CardKind::A,
-            'K' => CardKind::K,
-            'Q' => CardKind::Q,
-            'J' => CardKind::J,
-            'T' => CardKind::T,
-            '9' => CardKind::Nine,
-            '8' => CardKind::Eight,
-            '7' => CardKind::Seven,
-            '6' => CardKind::Six,
-            '5' => CardKind::Five,
-            '4' => CardKind::Four,
-            '3' => CardKind::Three,
-            '2' => CardKind::Two,
-            '1' => CardKind::One,
-            _ => panic!("Received unexpected char for CardType: {}", input),
+/// A Camel Cards variant: which cards exist and how strong they are, which card (if any)
+/// is wild, how many cards are in a hand and how ties are broken.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    /// Card labels from strongest to weakest.
+    order: Vec<char>,
+    wild: Option<char>,
+    hand_size: usize,
+    tie_break: TieBreak,
+}
+
+impl Rules {
+    pub fn new(order: &str, hand_size: usize) -> Rules {
+        Rules {
+            order: order.chars().collect(),
+            wild: None,
+            hand_size,
+            tie_break: TieBreak::CardOrder,
         }
     }
+
+    pub fn with_wild(mut self, wild: char) -> Rules {
+        self.wild = Some(wild);
+        self
+    }
+
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Rules {
+        self.tie_break = tie_break;
+        self
+    }
+
+    pub fn part_1() -> Rules {
+        Rules::new("AKQJT98765432", 5)
+    }
+
+    pub fn part_2() -> Rules {
+        Rules::new("AKQT98765432J", 5).with_wild('J')
+    }
+
+    /// Higher is stronger.
+    fn strength(&self, card: char) -> usize {
+        let position = self
+            .order
+            .iter()
+            .position(|&label| label == card)
+            .unwrap_or_else(|| panic!("Received unexpected char for card: {}", card));
+        self.order.len() - position
+    }
+
+    pub fn hand_kind(&self, hand: &str) -> HandKind {
+        let hand: Vec<char> = hand.chars().collect();
+        HandKind::from(&group_counts(&hand, self))
+    }
+
+    /// Classifies and ranks every hand, weakest first.
+    pub fn report(&self, input: &str) -> Vec<HandReport> {
+        let mut hands: Vec<_> = input
+            .split('\n')
+            .map(|line| HandAndBid::from(line, self))
+            .collect();
+        hands.sort_by(|a, b| a.sort_key.cmp(&b.sort_key));
+
+        hands
+            .into_iter()
+            .enumerate()
+            .map(|(index, hand)| {
+                let rank = (index + 1) as i64;
+                HandReport {
+                    kind: HandKind::from(&hand.sort_key.0),
+                    hand: hand.hand,
+                    substituted: hand.substituted,
+                    bid: hand.bid,
+                    rank,
+                    winnings: rank * hand.bid,
+                }
+            })
+            .collect()
+    }
+
+    pub fn winnings(&self, input: &str) -> i64 {
+        self.report(input).iter().map(|hand| hand.winnings).sum()
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ReportFormat {
+    Table,
+    Csv,
+}
+
+/// How a single hand was classified and what it contributed to the total winnings.
+#[derive(PartialEq, Eq, Debug)]
+pub struct HandReport {
+    pub hand: String,
+    /// The hand with every wild card replaced by the card that makes it strongest.
+    pub substituted: String,
+    pub kind: HandKind,
+    pub bid: i64,
+    pub rank: i64,
+    pub winnings: i64,
+}
+
+pub fn format_report(reports: &[HandReport], format: ReportFormat) -> String {
+    let mut result = String::new();
+
+    match format {
+        ReportFormat::Table => {
+            let width = reports
+                .iter()
+                .map(|report| report.hand.len())
+                .max()
+                .unwrap_or(0)
+                .max(4);
+            result += &format!(
+                "| {:<width$} | {:<width$} | {:<12} | {:>6} | {:>6} | {:>10} |\n",
+                "Hand", "Best", "Kind", "Bid", "Rank", "Winnings"
+            );
+            for report in reports {
+                result += &format!(
+                    "| {:<width$} | {:<width$} | {:<12} | {:>6} | {:>6} | {:>10} |\n",
+                    report.hand,
+                    report.substituted,
+                    format!("{:?}", report.kind),
+                    report.bid,
+                    report.rank,
+                    report.winnings
+                );
+            }
+        }
+        ReportFormat::Csv => {
+            result += "hand,best,kind,bid,rank,winnings\n";
+            for report in reports {
+                result += &format!(
+                    "{},{},{:?},{},{},{}\n",
+                    report.hand,
+                    report.substituted,
+                    report.kind,
+                    report.bid,
+                    report.rank,
+                    report.winnings
+                );
+            }
+        }
+    }
+
+    result
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
-enum HandKind {
+pub enum HandKind {
     FiveOfAKind,
     FourOfAKind,
     FullHouse,
@@ -69,174 +169,135 @@ enum HandKind {
 }
 
 impl HandKind {
-    fn from(cards: &[CardKind; 5]) -> HandKind {
-        let mut counts: HashMap<CardKind, usize> = HashMap::new();
-
-        for card in cards {
-            let entry = counts.entry(*card).or_insert(0);
-            *entry += 1;
-        }
-
-        let mut counts: Vec<usize> = counts.into_values().collect();
-        counts.sort_by(|a, b| b.cmp(a));
-        if counts.len() == 1 {
-            return HandKind::FiveOfAKind;
-        }
-
-        if counts[0] == 4 && counts[1] == 1 {
-            return HandKind::FourOfAKind;
-        }
-
-        if counts[0] == 3 && counts[1] == 2 {
-            return HandKind::FullHouse;
-        }
-
-        if counts[0] == 3 && counts[1] == 1 {
-            return HandKind::ThreeOfAKind;
+    /// Names the hand from its group sizes, sorted largest first. Hands larger than five
+    /// cards are named after their two largest groups.
+    fn from(counts: &[usize]) -> HandKind {
+        let first = counts.first().copied().unwrap_or(0);
+        let second = counts.get(1).copied().unwrap_or(0);
+
+        match (first, second) {
+            (5.., _) => HandKind::FiveOfAKind,
+            (4, _) => HandKind::FourOfAKind,
+            (3, 2..) => HandKind::FullHouse,
+            (3, _) => HandKind::ThreeOfAKind,
+            (2, 2..) => HandKind::TwoPair,
+            (2, _) => HandKind::OnePair,
+            _ => HandKind::HighCard,
         }
+    }
+}
 
-        if counts.len() == 3 && counts[0] == 2 && counts[1] == 2 {
-            return HandKind::TwoPair;
-        }
+/// Group sizes of the hand, largest first, with every wild card joining the largest group
+/// of natural cards. Comparing these lexicographically ranks hands of any size.
+fn group_counts(hand: &[char], rules: &Rules) -> Vec<usize> {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    let mut wilds = 0;
 
-        if counts.len() == 4 {
-            return HandKind::OnePair;
+    for &card in hand {
+        if Some(card) == rules.wild {
+            wilds += 1;
+        } else {
+            *counts.entry(card).or_insert(0) += 1;
         }
-
-        HandKind::HighCard
     }
 
-    fn from_with_jokers(cards: &[CardKind; 5]) -> HandKind {
-        let mut best = HandKind::from(cards);
-        for card in CardKind::VALUES {
-            if card == CardKind::J {
-                continue;
-            }
+    let mut counts: Vec<usize> = counts.into_values().collect();
+    counts.sort_by(|a, b| b.cmp(a));
+    match counts.first_mut() {
+        Some(largest) => *largest += wilds,
+        None => counts.push(wilds),
+    }
 
-            let alternate_hand = cards.map(|existing| {
-                if existing == CardKind::J {
-                    card
-                } else {
-                    existing
-                }
-            });
+    counts
+}
 
-            let kind = HandKind::from(&alternate_hand);
-            if kind < best {
-                best = kind;
-            }
+/// Replaces every wild card with the natural card it is grouped with by `group_counts`,
+/// preferring the strongest card when several groups are equally large. A hand of only
+/// wild cards becomes the strongest card that isn't wild.
+fn best_substitution(hand: &[char], rules: &Rules) -> String {
+    let Some(wild) = rules.wild else {
+        return hand.iter().collect();
+    };
+
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for &card in hand {
+        if card != wild {
+            *counts.entry(card).or_insert(0) += 1;
         }
-        best
     }
+    let best = counts
+        .into_iter()
+        .max_by_key(|&(card, count)| (count, rules.strength(card)))
+        .map(|(card, _)| card)
+        .or_else(|| rules.order.iter().copied().find(|&card| card != wild))
+        .unwrap_or(wild);
+
+    hand.iter()
+        .map(|&card| if card == wild { best } else { card })
+        .collect()
 }
 
 #[derive(Debug)]
 struct HandAndBid {
-    hand: [CardKind; 5],
-    kind: HandKind,
+    hand: String,
+    substituted: String,
+    /// Group sizes followed by card strengths in tie break order.
+    sort_key: (Vec<usize>, Vec<usize>),
     bid: i64,
 }
 
 impl HandAndBid {
-    fn from(line: &str, j_is_joker: bool) -> HandAndBid {
+    fn from(line: &str, rules: &Rules) -> HandAndBid {
         let split: Vec<_> = line.split(' ').collect();
-        let mut hand: [CardKind; 5] = [
-            CardKind::A,
-            CardKind::A,
-            CardKind::A,
-            CardKind::A,
-            CardKind::A,
-        ];
-        for (index, char) in split[0].chars().enumerate() {
-            hand[index] = CardKind::from(char);
+        let hand: Vec<char> = split[0].chars().collect();
+        if hand.len() != rules.hand_size {
+            panic!(
+                "Expected a hand of {} cards, got: {}",
+                rules.hand_size, split[0]
+            );
+        }
+
+        let counts = group_counts(&hand, rules);
+        let mut strengths: Vec<usize> = hand.iter().map(|&card| rules.strength(card)).collect();
+        if rules.tie_break == TieBreak::SortedHand {
+            let mut natural: HashMap<usize, usize> = HashMap::new();
+            for (&card, &strength) in hand.iter().zip(strengths.iter()) {
+                if Some(card) != rules.wild {
+                    *natural.entry(strength).or_insert(0) += 1;
+                }
+            }
+            strengths.sort_by_key(|strength| {
+                std::cmp::Reverse((natural.get(strength).copied().unwrap_or(0), *strength))
+            });
         }
 
-        let kind = if j_is_joker {
-            HandKind::from_with_jokers(&hand)
-        } else {
-            HandKind::from(&hand)
-        };
         let bid: i64 = split[1]
             .parse()
             .expect("Expected bid to be a valid number.");
 
-        HandAndBid { hand, kind, bid }
+        HandAndBid {
+            hand: split[0].to_owned(),
+            substituted: best_substitution(&hand, rules),
+            sort_key: (counts, strengths),
+            bid,
+        }
     }
 }
 
 pub fn day07_part_1(input: &str) -> i64 {
-    let mut hands: Vec<_> = input
-        .split('\n')
-        .map(|line| HandAndBid::from(line, false))
-        .collect();
-
-    hands.sort_by(|a, b| {
-        let ordering = a.kind.cmp(&b.kind);
-        if ordering != Ordering::Equal {
-            return ordering;
-        }
-
-        for i in 0..5 {
-            let ordering = a.hand[i].cmp(&b.hand[i]);
-            if ordering != Ordering::Equal {
-                return ordering;
-            }
-        }
-
-        Ordering::Equal
-    });
-
-    let mut result: i64 = 0;
-    for (index, hand) in hands.iter().rev().enumerate() {
-        let rank = (index + 1) as i64;
-        result += rank * hand.bid;
-    }
-
-    result
+    Rules::part_1().winnings(input)
 }
 
 pub fn day07_part_2(input: &str) -> i64 {
-    let mut hands: Vec<_> = input
-        .split('\n')
-        .map(|line| HandAndBid::from(line, true))
-        .collect();
-
-    hands.sort_by(|a, b| {
-        let ordering = a.kind.cmp(&b.kind);
-        if ordering != Ordering::Equal {
-            return ordering;
-        }
-
-        for i in 0..5 {
-            if a.hand[i] == CardKind::J && b.hand[i] != CardKind::J {
-                return Ordering::Greater;
-            }
-
-            if a.hand[i] != CardKind::J && b.hand[i] == CardKind::J {
-                return Ordering::Less;
-            }
-
-            let ordering = a.hand[i].cmp(&b.hand[i]);
-            if ordering != Ordering::Equal {
-                return ordering;
-            }
-        }
-
-        Ordering::Equal
-    });
-
-    let mut result: i64 = 0;
-    for (index, hand) in hands.iter().rev().enumerate() {
-        let rank = (index + 1) as i64;
-        result += rank * hand.bid;
-    }
-
-    result
+    Rules::part_2().winnings(input)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day07::{day07_part_1, day07_part_2};
+    use crate::day07::{
+        day07_part_1, day07_part_2, format_report, HandKind, HandReport, ReportFormat, Rules,
+        TieBreak,
+    };
 
     #[test]
     pub fn part1_example() {
@@ -265,4 +326,90 @@ QQQJA 483"
             5905
         );
     }
+
+    #[test]
+    pub fn hand_kinds() {
+        let rules = Rules::part_2();
+        assert_eq!(rules.hand_kind("JJJJJ"), HandKind::FiveOfAKind);
+        assert_eq!(rules.hand_kind("KTJJT"), HandKind::FourOfAKind);
+        assert_eq!(rules.hand_kind("2233J"), HandKind::FullHouse);
+        assert_eq!(rules.hand_kind("2345J"), HandKind::OnePair);
+        assert_eq!(Rules::part_1().hand_kind("KTJJT"), HandKind::TwoPair);
+
+        let rules = Rules::new("AKQJT98765432", 7).with_wild('2');
+        assert_eq!(rules.hand_kind("AAKKQ22"), HandKind::FourOfAKind);
+        assert_eq!(rules.hand_kind("AKQJT98"), HandKind::HighCard);
+    }
+
+    #[test]
+    pub fn variant_rules() {
+        // With sorted tie breaks the pair of kings beats the pair of queens:
+        let input = "AQQK2 10
+KKQA2 1";
+        assert_eq!(Rules::part_1().winnings(input), 21);
+        assert_eq!(
+            Rules::part_1()
+                .with_tie_break(TieBreak::SortedHand)
+                .winnings(input),
+            12
+        );
+
+        // A three card game where 2 is wild and the strongest card:
+        let rules = Rules::new("2AKQ", 3).with_wild('2');
+        assert_eq!(
+            rules.winnings(
+                "AAK 1
+2KQ 10
+22Q 100"
+            ),
+            1 + 20 + 300
+        );
+    }
+
+    #[test]
+    pub fn report() {
+        let reports = Rules::part_2().report(
+            "32T3K 765
+T55J5 684
+KK677 28
+KTJJT 220
+QQQJA 483",
+        );
+        assert_eq!(
+            reports[4],
+            HandReport {
+                hand: "KTJJT".to_owned(),
+                substituted: "KTTTT".to_owned(),
+                kind: HandKind::FourOfAKind,
+                bid: 220,
+                rank: 5,
+                winnings: 1100,
+            }
+        );
+        assert_eq!(
+            reports.iter().map(|report| report.winnings).sum::<i64>(),
+            5905
+        );
+
+        assert_eq!(
+            format_report(&reports[..2], ReportFormat::Csv),
+            "hand,best,kind,bid,rank,winnings
+32T3K,32T3K,OnePair,765,1,765
+KK677,KK677,TwoPair,28,2,56
+"
+        );
+        assert_eq!(
+            format_report(&reports[..1], ReportFormat::Table),
+            "| Hand  | Best  | Kind         |    Bid |   Rank |   Winnings |
+| 32T3K | 32T3K | OnePair      |    765 |      1 |        765 |
+"
+        );
+    }
+
+    #[test]
+    pub fn all_wild_substitution() {
+        let reports = Rules::new("2AKQ", 3).with_wild('2').report("222 1");
+        assert_eq!(reports[0].substituted, "AAA");
+        assert_eq!(reports[0].kind, HandKind::ThreeOfAKind);
+    }
 }
@@ -2,7 +2,7 @@ fn sequence_is_empty(seq: &[i64]) -> bool {
     seq.iter().all(|i| *i == 0)
 }
 
-fn sequence_to_diffs(seq: &Vec<i64>) -> Vec<i64> {
+fn sequence_to_diffs(seq: &[i64]) -> Vec<i64> {
     (1..seq.len())
         .map(|i| {
             let before = seq[i - 1];
@@ -43,7 +43,7 @@ fn sequences_prepend(seqs: &mut [Vec<i64>]) {
     let mut diff = 0;
     for seq in seqs.iter_mut().rev() {
         diff = seq[0] - diff;
-        Vec::splice(seq, 0..1, [diff]).last();
+        Vec::splice(seq, 0..1, [diff]).next_back();
     }
 }
 
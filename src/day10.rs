@@ -46,6 +46,27 @@ fn pipe_to_connections(pipe: char) -> Vec<Pos> {
     }
 }
 
+/// The pipe shape whose connections are exactly `offsets`, in any order.
+fn connections_to_pipe(offsets: &[Pos]) -> Option<char> {
+    ['|', '-', 'L', 'J', '7', 'F'].into_iter().find(|&pipe| {
+        let connections = pipe_to_connections(pipe);
+        connections.len() == offsets.len()
+            && connections.iter().all(|offset| offsets.contains(offset))
+    })
+}
+
+fn pipe_to_box_drawing(pipe: char) -> char {
+    match pipe {
+        '|' => '│',
+        '-' => '─',
+        'L' => '└',
+        'J' => '┘',
+        '7' => '┐',
+        'F' => '┌',
+        other => other,
+    }
+}
+
 #[derive(Debug)]
 struct Pipe {
     pos: Pos,
@@ -53,7 +74,25 @@ struct Pipe {
     connections: Vec<Pos>,
 }
 
-fn map_parse(input: &str) -> (HashMap<Pos, Pipe>, Pos) {
+#[derive(PartialEq, Eq, Debug)]
+pub enum LoopError {
+    MissingStart,
+    /// None of the pipes next to the start lead back to it.
+    NoClosedLoop,
+}
+
+/// The loop chosen through the start, and every other closed loop that passes through it.
+#[derive(PartialEq, Eq, Debug)]
+pub struct LoopReport {
+    /// The pipe shape under the start tile for the chosen loop.
+    pub start_pipe: char,
+    pub length: usize,
+    /// The start pipe shape and length of every closed loop, longest (chosen) first.
+    pub candidates: Vec<(char, usize)>,
+}
+
+/// Parses the map, connecting the start to every neighbour that connects back to it.
+fn map_parse_unresolved(input: &str) -> Result<(HashMap<Pos, Pipe>, Pos), LoopError> {
     let mut result: HashMap<Pos, Pipe> = HashMap::new();
     let mut start_pos: Option<Pos> = None;
 
@@ -82,7 +121,7 @@ fn map_parse(input: &str) -> (HashMap<Pos, Pipe>, Pos) {
         }
     }
 
-    let start_pos = start_pos.expect("Expected to find starting position.");
+    let start_pos = start_pos.ok_or(LoopError::MissingStart)?;
     let surrounding = [
         Pos { row: -1, col: 0 },
         Pos { row: 0, col: 1 },
@@ -92,7 +131,7 @@ fn map_parse(input: &str) -> (HashMap<Pos, Pipe>, Pos) {
     .iter()
     .map(|item| start_pos.add(item));
 
-    let mut starting_connections: Vec<Pos> = Vec::with_capacity(2);
+    let mut starting_connections: Vec<Pos> = Vec::with_capacity(4);
     for pos in surrounding {
         if let Some(pipe) = result.get(&pos) {
             for connection in pipe.connections.iter() {
@@ -108,7 +147,88 @@ fn map_parse(input: &str) -> (HashMap<Pos, Pipe>, Pos) {
         .expect("Expected to find Pipe for starting Pos.")
         .connections = starting_connections;
 
-    (result, start_pos)
+    Ok((result, start_pos))
+}
+
+/// Follows the pipes from the start through `first`, returning the path if it arrives
+/// back at the start rather than reaching a dead end.
+fn walk_loop(map: &HashMap<Pos, Pipe>, start_pos: Pos, first: Pos) -> Option<Vec<Pos>> {
+    let mut path = vec![start_pos];
+    let mut previous = start_pos;
+    let mut current = first;
+
+    while current != start_pos {
+        let pipe = map.get(&current)?;
+        if !pipe.connections.contains(&previous) || path.len() > map.len() {
+            return None;
+        }
+        path.push(current);
+        let next = *pipe
+            .connections
+            .iter()
+            .find(|&&connection| connection != previous)?;
+        previous = current;
+        current = next;
+    }
+
+    Some(path)
+}
+
+/// Every closed loop through the start, longest first.
+fn map_loops(map: &HashMap<Pos, Pipe>, start_pos: Pos) -> Vec<Vec<Pos>> {
+    let mut loops: Vec<Vec<Pos>> = map[&start_pos]
+        .connections
+        .iter()
+        .filter_map(|&first| walk_loop(map, start_pos, first))
+        // Each loop is found once in each direction, so keep only one of them:
+        .filter(|path| path[1] < path[path.len() - 1])
+        .collect();
+    loops.sort_by_key(|path| std::cmp::Reverse(path.len()));
+    loops
+}
+
+fn loop_start_pipe(path: &[Pos]) -> char {
+    let offsets = [
+        path[1].subtract(&path[0]),
+        path[path.len() - 1].subtract(&path[0]),
+    ];
+    connections_to_pipe(&offsets).expect("Expected loop to leave the start twice.")
+}
+
+/// The map, the start and the loop through it.
+type ResolvedMap = (HashMap<Pos, Pipe>, Pos, Vec<Pos>);
+
+/// Parses the map and replaces the start with the pipe shape of the longest loop
+/// through it, returning that loop in the order it is walked.
+fn map_parse(input: &str) -> Result<ResolvedMap, LoopError> {
+    let (mut map, start_pos) = map_parse_unresolved(input)?;
+    let path = map_loops(&map, start_pos)
+        .into_iter()
+        .next()
+        .ok_or(LoopError::NoClosedLoop)?;
+
+    let start = map
+        .get_mut(&start_pos)
+        .expect("Expected to find Pipe for starting Pos.");
+    start.kind = loop_start_pipe(&path);
+    start.connections = vec![path[1], path[path.len() - 1]];
+
+    Ok((map, start_pos, path))
+}
+
+pub fn day10_find_loop(input: &str) -> Result<LoopReport, LoopError> {
+    let (map, start_pos) = map_parse_unresolved(input)?;
+    let candidates: Vec<(char, usize)> = map_loops(&map, start_pos)
+        .iter()
+        .map(|path| (loop_start_pipe(path), path.len()))
+        .collect();
+    let (start_pipe, length) = *candidates.first().ok_or(LoopError::NoClosedLoop)?;
+
+    Ok(LoopReport {
+        start_pipe,
+        length,
+        candidates,
+    })
 }
 
 fn distance_count(map: &HashMap<Pos, Pipe>, start_pos: Pos) -> HashMap<Pos, i64> {
@@ -400,7 +520,7 @@ fn map_remove_junk(map: &mut HashMap<Pos, Pipe>, start_pos: Pos) {
 }
 
 pub fn day10_part_1(input: &str) -> i64 {
-    let (map, start_pos) = map_parse(input);
+    let (map, start_pos, _) = map_parse(input).expect("Expected a closed loop through the start.");
     let distances = distance_count(&map, start_pos);
     *distances
         .values()
@@ -408,8 +528,18 @@ pub fn day10_part_1(input: &str) -> i64 {
         .expect("Expected to find maximum distance.")
 }
 
-pub fn day10_part_2(input: &str) -> i64 {
-    let (mut map, start_pos) = map_parse(input);
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum EnclosedAlgorithm {
+    /// Double the map so gaps between pipes become tiles, then flood fill from outside.
+    FloodFill,
+    /// Take the loop's area with the shoelace formula, then recover the interior tile
+    /// count with Pick's theorem.
+    Shoelace,
+    /// Scan each row, toggling inside/outside whenever a pipe heading north is crossed.
+    Scanline,
+}
+
+fn enclosed_flood_fill(mut map: HashMap<Pos, Pipe>, start_pos: Pos) -> i64 {
     map_remove_junk(&mut map, start_pos);
     let mut doubled = map_double(&map);
     map_extend(&mut doubled);
@@ -427,9 +557,154 @@ pub fn day10_part_2(input: &str) -> i64 {
     )
 }
 
+fn enclosed_shoelace(path: &[Pos]) -> i64 {
+    let mut double_area: i64 = 0;
+    for (index, a) in path.iter().enumerate() {
+        let b = path[(index + 1) % path.len()];
+        double_area += a.col * b.row - b.col * a.row;
+    }
+
+    // Pick's theorem: area = interior + boundary / 2 - 1
+    double_area.abs() / 2 - path.len() as i64 / 2 + 1
+}
+
+/// Tiles enclosed by `path`, found by scanning each row and toggling inside/outside
+/// whenever a pipe heading north is crossed.
+fn inside_tiles(map: &HashMap<Pos, Pipe>, path: &HashSet<Pos>) -> HashSet<Pos> {
+    let rows = map.keys().map(|pos| pos.row).max().unwrap_or(0);
+    let cols = map.keys().map(|pos| pos.col).max().unwrap_or(0);
+    let mut result = HashSet::new();
+
+    for row in 0..=rows {
+        let mut inside = false;
+        for col in 0..=cols {
+            let pos = Pos { row, col };
+            if path.contains(&pos) {
+                let north = pos.add(&Pos { row: -1, col: 0 });
+                if map[&pos].connections.contains(&north) {
+                    inside = !inside;
+                }
+            } else if inside {
+                result.insert(pos);
+            }
+        }
+    }
+
+    result
+}
+
+fn enclosed_scanline(map: &HashMap<Pos, Pipe>, path: Vec<Pos>) -> i64 {
+    let path: HashSet<Pos> = path.into_iter().collect();
+    inside_tiles(map, &path).len() as i64
+}
+
+/// Redraws the map with the loop in box drawing characters and every other tile marked
+/// `I` when enclosed by the loop or `O` otherwise. With `dim_junk`, the marked tiles are
+/// wrapped in ANSI faint escapes so the loop stands out in a terminal.
+pub fn day10_render(input: &str, dim_junk: bool) -> String {
+    let (map, _, path) = map_parse(input).expect("Expected a closed loop through the start.");
+    let path: HashSet<Pos> = path.into_iter().collect();
+    let inside = inside_tiles(&map, &path);
+    let rows = map.keys().map(|pos| pos.row).max().unwrap_or(0);
+    let cols = map.keys().map(|pos| pos.col).max().unwrap_or(0);
+    let mut result = String::new();
+
+    for row in 0..=rows {
+        for col in 0..=cols {
+            let pos = Pos { row, col };
+            if path.contains(&pos) {
+                result.push(pipe_to_box_drawing(map[&pos].kind));
+                continue;
+            }
+
+            let mark = if inside.contains(&pos) { 'I' } else { 'O' };
+            if dim_junk {
+                result += &format!("\x1b[2m{}\x1b[0m", mark);
+            } else {
+                result.push(mark);
+            }
+        }
+        if row != rows {
+            result.push('\n');
+        }
+    }
+
+    result
+}
+
+pub fn day10_enclosed(input: &str, algorithm: EnclosedAlgorithm) -> i64 {
+    let (map, start_pos, path) =
+        map_parse(input).expect("Expected a closed loop through the start.");
+    match algorithm {
+        EnclosedAlgorithm::FloodFill => enclosed_flood_fill(map, start_pos),
+        EnclosedAlgorithm::Shoelace => enclosed_shoelace(&path),
+        EnclosedAlgorithm::Scanline => enclosed_scanline(&map, path),
+    }
+}
+
+pub fn day10_part_2(input: &str) -> i64 {
+    day10_enclosed(input, EnclosedAlgorithm::FloodFill)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::day10::{day10_part_1, day10_part_2};
+    use crate::day10::{
+        day10_enclosed, day10_find_loop, day10_part_1, day10_part_2, day10_render, map_parse,
+        EnclosedAlgorithm, LoopError, LoopReport,
+    };
+
+    const PART2_EXAMPLES: [(&str, i64); 4] = [
+        (
+            "...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+...........",
+            4,
+        ),
+        (
+            "..........
+.S------7.
+.|F----7|.
+.||....||.
+.||....||.
+.|L-7F-J|.
+.|..||..|.
+.L--JL--J.
+..........",
+            4,
+        ),
+        (
+            ".F----7F7F7F7F-7....
+.|F--7||||||||FJ....
+.||.FJ||||||||L7....
+FJL7L7LJLJ||LJ.L-7..
+L--J.L7...LJS7F-7L7.
+....F-J..F7FJ|L7L7L7
+....L7.F7||L7|.L7L7|
+.....|FJLJ|FJ|F7|.LJ
+....FJL-7.||.||||...
+....L---J.LJ.LJLJ...",
+            8,
+        ),
+        (
+            "FF7FSF7F7F7F7F7F---7
+L|LJ||||||||||||F--J
+FL-7LJLJ||||||LJL-77
+F--JF--7||LJLJ7F7FJ-
+L---JF-JLJ.||-FJLJJ7
+|F|F-JF---7F7-L7L|7|
+|FFJF7L7F-JF7|JL---7
+7-L-JL7||F7|L7F-7F7|
+L.L7LFJ|||||FJL7||LJ
+L7JLJL-JLJLJL--JLJ.L",
+            10,
+        ),
+    ];
 
     #[test]
     pub fn part1_example() {
@@ -539,4 +814,119 @@ L7JLJL-JLJLJL--JLJ.L"
             10
         );
     }
+
+    #[test]
+    pub fn enclosed_algorithms_agree() {
+        for (input, expected) in PART2_EXAMPLES {
+            for algorithm in [
+                EnclosedAlgorithm::FloodFill,
+                EnclosedAlgorithm::Shoelace,
+                EnclosedAlgorithm::Scanline,
+            ] {
+                assert_eq!(
+                    day10_enclosed(input, algorithm),
+                    expected,
+                    "{:?}",
+                    algorithm
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn start_inference() {
+        let expected = ['F', 'F', 'F', 'F', '7'];
+        let inputs = [
+            ".....\n.S-7.\n.|.|.\n.L-J.\n.....",
+            "-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF",
+            "..F7.\n.FJ|.\nSJ.L7\n|F--J\nLJ...",
+            PART2_EXAMPLES[1].0,
+            PART2_EXAMPLES[3].0,
+        ];
+        for (input, expected) in inputs.into_iter().zip(expected) {
+            let (map, start_pos, _) =
+                map_parse(input).expect("Expected a closed loop through the start.");
+            assert_eq!(map[&start_pos].kind, expected);
+        }
+    }
+
+    #[test]
+    pub fn render() {
+        assert_eq!(
+            day10_render("-L|F7\n7S-7|\nL|7||\n-L-J|\nL|-JF", false),
+            "OOOOO
+O┌─┐O
+O│I│O
+O└─┘O
+OOOOO"
+        );
+        assert_eq!(
+            day10_render(".S7\n.LJ", true),
+            "\x1b[2mO\x1b[0m┌┐\n\x1b[2mO\x1b[0m└┘"
+        );
+    }
+
+    #[test]
+    pub fn dead_ends_at_start() {
+        let input = ".|...
+-S-7.
+.|.|.
+.L-J.
+.....";
+        assert_eq!(
+            day10_find_loop(input),
+            Ok(LoopReport {
+                start_pipe: 'F',
+                length: 8,
+                candidates: vec![('F', 8)],
+            })
+        );
+        assert_eq!(day10_part_1(input), 4);
+        assert_eq!(day10_part_2(input), 1);
+    }
+
+    #[test]
+    pub fn multiple_loops_through_start() {
+        let input = "F-7...
+|.|...
+L-S--7
+..|..|
+..L--J";
+        assert_eq!(
+            day10_find_loop(input),
+            Ok(LoopReport {
+                start_pipe: 'F',
+                length: 10,
+                candidates: vec![('F', 10), ('J', 8)],
+            })
+        );
+        assert_eq!(day10_part_1(input), 5);
+        for algorithm in [
+            EnclosedAlgorithm::FloodFill,
+            EnclosedAlgorithm::Shoelace,
+            EnclosedAlgorithm::Scanline,
+        ] {
+            assert_eq!(day10_enclosed(input, algorithm), 2, "{:?}", algorithm);
+        }
+    }
+
+    #[test]
+    pub fn no_closed_loop() {
+        assert_eq!(
+            day10_find_loop(
+                ".....
+.S-7.
+.|.|.
+.L-..
+....."
+            ),
+            Err(LoopError::NoClosedLoop)
+        );
+        assert_eq!(day10_find_loop("S...."), Err(LoopError::NoClosedLoop));
+        assert_eq!(
+            day10_find_loop("F-7\n|.|\nL-J"),
+            Err(LoopError::MissingStart)
+        );
+        assert!(map_parse("-S-").is_err());
+    }
 }
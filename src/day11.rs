@@ -1,7 +1,5 @@
 use std::collections::HashSet;
 
-use crate::fnv1::BuildFnv1Hasher;
-
 #[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy, Debug)]
 struct Pos {
     row: i64,
@@ -14,10 +12,8 @@ impl Pos {
     }
 }
 
-struct Galaxies {
-    set: HashSet<Pos, BuildFnv1Hasher>,
-    rows_populated: HashSet<i64, BuildFnv1Hasher>,
-    cols_populated: HashSet<i64, BuildFnv1Hasher>,
+pub struct Galaxies {
+    positions: Vec<Pos>,
     row_max: i64,
     col_max: i64,
 }
@@ -25,18 +21,14 @@ struct Galaxies {
 impl Galaxies {
     fn new(capacity: usize) -> Galaxies {
         Galaxies {
-            set: HashSet::with_capacity_and_hasher(capacity, BuildFnv1Hasher),
-            rows_populated: HashSet::with_capacity_and_hasher(capacity / 2, BuildFnv1Hasher),
-            cols_populated: HashSet::with_capacity_and_hasher(capacity / 2, BuildFnv1Hasher),
+            positions: Vec::with_capacity(capacity),
             row_max: 0,
             col_max: 0,
         }
     }
 
     fn insert(&mut self, pos: Pos) {
-        self.set.insert(pos);
-        self.rows_populated.insert(pos.row);
-        self.cols_populated.insert(pos.col);
+        self.positions.push(pos);
         if pos.row > self.row_max {
             self.row_max = pos.row;
         }
@@ -45,12 +37,86 @@ impl Galaxies {
         }
     }
 
-    fn is_row_empty(&self, row: &i64) -> bool {
-        !self.rows_populated.contains(row)
+    pub fn parse(input: &str) -> Galaxies {
+        map_parse(input)
+    }
+
+    /// A copy with every empty row and column replaced by `expansion` of them.
+    pub fn expand(&self, expansion: i64) -> Galaxies {
+        map_expand(self, expansion)
+    }
+
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    /// The distance from galaxy `a` to galaxy `b`, numbered from zero in reading order.
+    pub fn distance(&self, a: usize, b: usize) -> i64 {
+        self.positions[a].distance(&self.positions[b])
     }
 
-    fn is_col_empty(&self, col: &i64) -> bool {
-        !self.cols_populated.contains(col)
+    /// For each galaxy, the closest other galaxy and its distance, preferring the lowest
+    /// numbered galaxy on ties.
+    pub fn nearest_neighbours(&self) -> Vec<Option<(usize, i64)>> {
+        let mut by_row: Vec<usize> = (0..self.positions.len()).collect();
+        by_row.sort_by_key(|&index| (self.positions[index].row, index));
+
+        let mut result = vec![None; self.positions.len()];
+        for (sorted_index, &galaxy) in by_row.iter().enumerate() {
+            let mut best: Option<(usize, i64)> = None;
+            self.nearest_in(galaxy, by_row[..sorted_index].iter().rev(), &mut best);
+            self.nearest_in(galaxy, by_row[sorted_index + 1..].iter(), &mut best);
+            result[galaxy] = best;
+        }
+
+        result
+    }
+
+    /// Updates `best` with the closest of `candidates`, which must be sorted by their row
+    /// distance from `galaxy`.
+    fn nearest_in<'a>(
+        &self,
+        galaxy: usize,
+        candidates: impl Iterator<Item = &'a usize>,
+        best: &mut Option<(usize, i64)>,
+    ) {
+        let pos = self.positions[galaxy];
+        for &other in candidates {
+            let other_pos = self.positions[other];
+            if let Some((best_index, best_distance)) = *best {
+                // Nothing further away in this direction can be closer:
+                if (other_pos.row - pos.row).abs() > best_distance {
+                    break;
+                }
+                if (pos.distance(&other_pos), other) >= (best_distance, best_index) {
+                    continue;
+                }
+            }
+            *best = Some((other, pos.distance(&other_pos)));
+        }
+    }
+
+    /// Every pairwise distance as CSV, with a header row and column of galaxy numbers.
+    pub fn distance_matrix_csv(&self) -> String {
+        let mut result = String::from("galaxy");
+        for index in 0..self.positions.len() {
+            result += &format!(",{}", index);
+        }
+        result.push('\n');
+
+        for a in 0..self.positions.len() {
+            result += &a.to_string();
+            for b in 0..self.positions.len() {
+                result += &format!(",{}", self.distance(a, b));
+            }
+            result.push('\n');
+        }
+
+        result
     }
 
     fn max(&self) -> Pos {
@@ -59,6 +125,34 @@ impl Galaxies {
             col: self.col_max,
         }
     }
+
+    /// For each row, how many empty rows are above it.
+    fn empty_rows_before(&self) -> Vec<i64> {
+        empty_before(self.positions.iter().map(|pos| pos.row), self.row_max)
+    }
+
+    /// For each column, how many empty columns are left of it.
+    fn empty_cols_before(&self) -> Vec<i64> {
+        empty_before(self.positions.iter().map(|pos| pos.col), self.col_max)
+    }
+}
+
+fn empty_before(populated: impl Iterator<Item = i64>, max: i64) -> Vec<i64> {
+    let mut is_populated = vec![false; max as usize + 1];
+    for index in populated {
+        is_populated[index as usize] = true;
+    }
+
+    let mut result = Vec::with_capacity(is_populated.len());
+    let mut empty = 0;
+    for is_populated in is_populated {
+        result.push(empty);
+        if !is_populated {
+            empty += 1;
+        }
+    }
+
+    result
 }
 
 fn map_parse(input: &str) -> Galaxies {
@@ -79,77 +173,76 @@ fn map_parse(input: &str) -> Galaxies {
     result
 }
 
-#[allow(unused_variables, clippy::let_unit_value)]
+/// Replaces every empty row and column with `expansion` empty rows or columns.
 fn map_expand(map: &Galaxies, expansion: i64) -> Galaxies {
-    let mut expanded_columns: Galaxies = Galaxies::new(map.set.len());
-    let max = map.max();
-    let mut col_dst: i64 = 0;
-    for col_src in 0..=max.col {
-        if map.is_col_empty(&col_src) {
-            col_dst += expansion;
-        } else {
-            for pos_src in map.set.iter() {
-                if pos_src.col == col_src {
-                    expanded_columns.insert(Pos {
-                        row: pos_src.row,
-                        col: col_dst,
-                    })
-                }
-            }
+    let empty_rows_before = map.empty_rows_before();
+    let empty_cols_before = map.empty_cols_before();
+    let mut result = Galaxies::new(map.positions.len());
 
-            col_dst += 1;
-        }
+    for pos in map.positions.iter() {
+        result.insert(Pos {
+            row: pos.row + empty_rows_before[pos.row as usize] * (expansion - 1),
+            col: pos.col + empty_cols_before[pos.col as usize] * (expansion - 1),
+        });
     }
 
-    // Prevent incorrect references and mutations:
-    let max = Pos {
-        row: max.row,
-        col: col_dst,
-    };
-    let expanded_columns = expanded_columns;
-    let col_dst = ();
-    let map = ();
-
-    let mut expanded_rows: Galaxies = Galaxies::new(expanded_columns.set.len());
-    let mut row_dst: i64 = 0;
-    for row_src in 0..=max.row {
-        if expanded_columns.is_row_empty(&row_src) {
-            row_dst += expansion;
-        } else {
-            for pos_src in expanded_columns.set.iter() {
-                if pos_src.row == row_src {
-                    expanded_rows.insert(Pos {
-                        row: row_dst,
-                        col: pos_src.col,
-                    })
-                }
-            }
-            row_dst += 1;
-        }
+    result
+}
+
+/// The sum of `|a - b|` over every pair of values.
+fn axis_sum_distance_pairs(mut values: Vec<i64>) -> i64 {
+    values.sort_unstable();
+
+    let mut result = 0;
+    let mut sum_before = 0;
+    for (index, value) in values.into_iter().enumerate() {
+        result += value * index as i64 - sum_before;
+        sum_before += value;
     }
 
-    expanded_rows
+    result
 }
 
 fn map_sum_distance_pairs(map: &Galaxies) -> i64 {
-    let mut result = 0;
-    let galaxies: Vec<_> = map.set.iter().collect();
+    axis_sum_distance_pairs(map.positions.iter().map(|pos| pos.row).collect())
+        + axis_sum_distance_pairs(map.positions.iter().map(|pos| pos.col).collect())
+}
 
-    for (i, galaxy_a) in galaxies.iter().enumerate() {
-        for galaxy_b in galaxies.iter().skip(i + 1) {
-            result += galaxy_a.distance(galaxy_b);
-        }
-    }
+/// The sum of distances between every pair of galaxies, for each expansion factor.
+///
+/// Every empty row or column between two galaxies adds `expansion - 1` to their distance,
+/// so the sum is linear in the expansion and only needs to be computed once.
+pub fn day11_sum_distances(input: &str, expansions: &[i64]) -> Vec<i64> {
+    let map = map_parse(input);
+    let empty_rows_before = map.empty_rows_before();
+    let empty_cols_before = map.empty_cols_before();
 
-    result
+    let unexpanded = map_sum_distance_pairs(&map);
+    let empty_between = axis_sum_distance_pairs(
+        map.positions
+            .iter()
+            .map(|pos| empty_rows_before[pos.row as usize])
+            .collect(),
+    ) + axis_sum_distance_pairs(
+        map.positions
+            .iter()
+            .map(|pos| empty_cols_before[pos.col as usize])
+            .collect(),
+    );
+
+    expansions
+        .iter()
+        .map(|expansion| unexpanded + empty_between * (expansion - 1))
+        .collect()
 }
 
 #[allow(dead_code)]
 fn map_print(map: &Galaxies) {
     let max = map.max();
+    let set: HashSet<Pos> = map.positions.iter().cloned().collect();
     for row in 0..=max.row {
         for col in 0..=max.col {
-            let char = if map.set.get(&Pos { row, col }).is_some() {
+            let char = if set.contains(&Pos { row, col }) {
                 '#'
             } else {
                 '.'
@@ -175,7 +268,21 @@ pub fn day11_part_2(input: &str) -> i64 {
 
 #[cfg(test)]
 mod tests {
-    use crate::day11::{day11_part_1, map_expand, map_parse, map_sum_distance_pairs};
+    use crate::day11::{
+        day11_part_1, day11_sum_distances, map_expand, map_parse, map_sum_distance_pairs, Galaxies,
+        Pos,
+    };
+
+    const EXAMPLE: &str = "...#......
+.......#..
+#.........
+..........
+......#...
+.#........
+.........#
+..........
+.......#..
+#...#.....";
 
     #[test]
     pub fn part1_example() {
@@ -236,4 +343,69 @@ mod tests {
             8410
         );
     }
+
+    #[test]
+    pub fn multiple_expansions() {
+        assert_eq!(
+            day11_sum_distances(EXAMPLE, &[1, 2, 10, 100]),
+            vec![292, 374, 1030, 8410]
+        );
+
+        // Compare against the quadratic sum over every pair:
+        let map = map_expand(&map_parse(EXAMPLE), 1000000);
+        let mut expected = 0;
+        for (i, a) in map.positions.iter().enumerate() {
+            for b in map.positions.iter().skip(i + 1) {
+                expected += a.distance(b);
+            }
+        }
+        assert_eq!(day11_sum_distances(EXAMPLE, &[1000000]), vec![expected]);
+        assert_eq!(
+            map.positions[0],
+            Pos {
+                row: 0,
+                col: 2 + 1000000
+            }
+        );
+    }
+
+    #[test]
+    pub fn pair_distances() {
+        let galaxies = Galaxies::parse(EXAMPLE).expand(2);
+        assert_eq!(galaxies.len(), 9);
+        assert_eq!(galaxies.distance(4, 8), 9);
+        assert_eq!(galaxies.distance(0, 6), 15);
+        assert_eq!(galaxies.distance(2, 5), 17);
+        assert_eq!(galaxies.distance(7, 8), 5);
+        assert_eq!(Galaxies::parse(EXAMPLE).expand(10).distance(7, 8), 13);
+    }
+
+    #[test]
+    pub fn nearest_neighbours() {
+        let galaxies = Galaxies::parse(EXAMPLE).expand(2);
+        let nearest = galaxies.nearest_neighbours();
+        for (a, nearest) in nearest.into_iter().enumerate() {
+            let expected = (0..galaxies.len())
+                .filter(|&b| b != a)
+                .map(|b| (galaxies.distance(a, b), b))
+                .min()
+                .map(|(distance, b)| (b, distance));
+            assert_eq!(nearest, expected);
+        }
+
+        assert_eq!(Galaxies::parse("#").nearest_neighbours(), vec![None]);
+    }
+
+    #[test]
+    pub fn distance_matrix_csv() {
+        assert_eq!(
+            Galaxies::parse("#..\n...\n..#")
+                .expand(2)
+                .distance_matrix_csv(),
+            "galaxy,0,1
+0,0,6
+1,6,0
+"
+        );
+    }
 }
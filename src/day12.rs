@@ -1,201 +1,217 @@
-use std::collections::HashMap;
-
 const KNOWN_GOOD: u8 = 46; // ASCII .
 const KNOWN_BAD: u8 = 35; // ASCII #
 const UNKNOWN: u8 = 63; // ASCII ?
 
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
-enum RecursionState {
-    Continue,
-    NeedHash(RecordInt),
-}
-
 type SpringType = u8;
 type RecordInt = u8;
-type CacheMap<'a> = HashMap<(&'a [SpringType], &'a [RecordInt], RecursionState), u64>;
 
-fn recurse_arrangements_memoized<'a>(
-    springs: &'a [SpringType],
-    records: &'a [RecordInt],
-    state: RecursionState,
-    cache: &mut CacheMap<'a>,
-) -> u64 {
-    let key = (springs, records, state);
-    if let Some(result) = cache.get(&key) {
-        *result
-    } else {
-        let result = recurse_arrangements(springs, records, state, cache);
-        cache.insert(key, result);
-        result
+fn parse_line(line: &str) -> (&[SpringType], Vec<RecordInt>) {
+    let (springs, records) = line
+        .split_once(' ')
+        .expect("Line should have springs and records.");
+    let records = records
+        .split(',')
+        .map(|item| item.parse().expect("Records should be parseable."))
+        .collect();
+
+    (springs.as_bytes(), records)
+}
+
+fn unfold(
+    springs: &[SpringType],
+    records: &[RecordInt],
+    factor: usize,
+) -> (Vec<SpringType>, Vec<RecordInt>) {
+    let mut unfolded_springs = Vec::with_capacity((springs.len() + 1) * factor);
+    for i in 0..factor {
+        if i != 0 {
+            unfolded_springs.push(UNKNOWN);
+        }
+        unfolded_springs.extend_from_slice(springs);
     }
+
+    (unfolded_springs, records.repeat(factor))
 }
 
-fn recurse_arrangements<'a>(
+/// Every way of resolving the unknown springs in a row so that it matches its records.
+///
+/// Arrangements are ordered lexicographically by their ASCII representation, so `#`
+/// sorts before `.`.
+pub struct Arrangements<'a> {
     springs: &'a [SpringType],
     records: &'a [RecordInt],
-    state: RecursionState,
-    cache: &mut CacheMap<'a>,
-) -> u64 {
-    use RecursionState::*;
-
-    match state {
-        Continue => {
-            if springs.is_empty() && records.is_empty() {
-                return 1;
-            } else if springs.is_empty() {
-                return 0;
+    /// The length of the run of possibly damaged springs starting at each index.
+    could_be_bad: Vec<usize>,
+    /// `ways[i * (records.len() + 1) + j]` is the number of arrangements of `springs[i..]`
+    /// matching `records[j..]`, where spring `i` is not continuing an earlier group.
+    ways: Vec<u128>,
+}
+
+impl<'a> Arrangements<'a> {
+    pub fn new(springs: &'a [SpringType], records: &'a [RecordInt]) -> Arrangements<'a> {
+        let mut could_be_bad = vec![0; springs.len() + 1];
+        for i in (0..springs.len()).rev() {
+            if springs[i] != KNOWN_GOOD {
+                could_be_bad[i] = could_be_bad[i + 1] + 1;
             }
+        }
 
-            match springs[0] {
-                UNKNOWN => {
-                    let arrangements_when_placing = if records.is_empty() {
-                        0
-                    } else {
-                        recurse_arrangements_memoized(
-                            &springs[1..],
-                            &records[1..],
-                            NeedHash(records[0] - 1),
-                            cache,
-                        )
-                    };
-                    let arrangements_when_not_placing =
-                        recurse_arrangements_memoized(&springs[1..], records, Continue, cache);
-
-                    arrangements_when_placing + arrangements_when_not_placing
-                }
-                KNOWN_GOOD => {
-                    recurse_arrangements_memoized(&springs[1..], records, Continue, cache)
+        let mut arrangements = Arrangements {
+            springs,
+            records,
+            could_be_bad,
+            ways: vec![0; (springs.len() + 1) * (records.len() + 1)],
+        };
+
+        let index = |i: usize, j: usize| i * (records.len() + 1) + j;
+        arrangements.ways[index(springs.len(), records.len())] = 1;
+        for i in (0..springs.len()).rev() {
+            for j in 0..=records.len() {
+                let mut ways = 0;
+                if springs[i] != KNOWN_BAD {
+                    ways += arrangements.ways[index(i + 1, j)];
                 }
-                KNOWN_BAD => {
-                    if records.is_empty() {
-                        0
-                    } else {
-                        recurse_arrangements_memoized(
-                            &springs[1..],
-                            &records[1..],
-                            NeedHash(records[0] - 1),
-                            cache,
-                        )
-                    }
+                if let Some(next) = arrangements.group_end(i, j) {
+                    ways += arrangements.ways[index(next, j + 1)];
                 }
-                bad => unreachable!("Unexpected character: {}", bad),
+                arrangements.ways[index(i, j)] = ways;
+            }
+        }
+
+        arrangements
+    }
+
+    /// If group `j` can start at spring `i`, returns the index just past it and the
+    /// operational spring that must follow it.
+    fn group_end(&self, i: usize, j: usize) -> Option<usize> {
+        if j < self.records.len() {
+            let end = i + self.records[j] as usize;
+            if i + self.could_be_bad[i] >= end && self.springs.get(end) != Some(&KNOWN_BAD) {
+                return Some((end + 1).min(self.springs.len()));
             }
         }
-        NeedHash(placing) => {
-            // If we're out of items to place, we need to find a dot or the end of the input:
-            if placing == 0 {
-                if springs.is_empty() {
-                    if records.is_empty() {
-                        1
-                    } else {
-                        0
-                    }
-                } else if springs[0] == KNOWN_BAD {
-                    0
-                } else {
-                    recurse_arrangements_memoized(&springs[1..], records, Continue, cache)
+        None
+    }
+
+    fn ways(&self, i: usize, j: usize) -> u128 {
+        self.ways[i * (self.records.len() + 1) + j]
+    }
+
+    pub fn count(&self) -> u128 {
+        self.ways(0, 0)
+    }
+
+    /// Returns the arrangement at `index` in lexicographic order, or `None` if there are
+    /// not that many arrangements.
+    pub fn nth(&self, mut index: u128) -> Option<String> {
+        if index >= self.count() {
+            return None;
+        }
+
+        let mut result = Vec::with_capacity(self.springs.len());
+        let (mut i, mut j) = (0, 0);
+        while i < self.springs.len() {
+            let group_end = self.group_end(i, j);
+            let arrangements_when_placing = group_end.map_or(0, |next| self.ways(next, j + 1));
+            if index < arrangements_when_placing {
+                let next = group_end.expect("Group should fit when it has arrangements.");
+                let group_len = self.records[j] as usize;
+                result.extend(std::iter::repeat_n(KNOWN_BAD, group_len));
+                if next > i + group_len {
+                    result.push(KNOWN_GOOD);
                 }
-            } else if springs.is_empty() || springs[0] == KNOWN_GOOD {
-                0
+                i = next;
+                j += 1;
             } else {
-                recurse_arrangements_memoized(&springs[1..], records, NeedHash(placing - 1), cache)
+                index -= arrangements_when_placing;
+                result.push(KNOWN_GOOD);
+                i += 1;
+            }
+        }
+
+        Some(String::from_utf8(result).expect("Arrangement should be ASCII."))
+    }
+
+    /// Picks an arrangement uniformly at random, drawing random numbers from `random` as
+    /// needed. Returns `None` if the row has no arrangements.
+    pub fn sample(&self, mut random: impl FnMut() -> u64) -> Option<String> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+
+        // Reject draws from the final partial block to avoid modulo bias:
+        let limit = u128::MAX - u128::MAX % count;
+        loop {
+            let draw = (random() as u128) << 64 | random() as u128;
+            if draw < limit {
+                return self.nth(draw % count);
             }
         }
     }
 }
 
-pub fn day12_part_1(input: &str) -> u64 {
-    let mut result: u64 = 0;
+impl<'a> IntoIterator for Arrangements<'a> {
+    type Item = String;
+    type IntoIter = ArrangementsIter<'a>;
 
-    for line in input.split('\n') {
-        let mut cache: CacheMap = HashMap::new();
-        let split: Vec<_> = line.split(' ').collect();
-        let records: Vec<RecordInt> = split[1]
-            .split(',')
-            .map(|item| item.parse().expect("Records should be parseable."))
-            .collect();
-        result += recurse_arrangements(
-            split[0].as_bytes(),
-            &records,
-            RecursionState::Continue,
-            &mut cache,
-        )
+    fn into_iter(self) -> Self::IntoIter {
+        let count = self.count();
+        ArrangementsIter {
+            arrangements: self,
+            next: 0,
+            count,
+        }
     }
+}
 
-    result
+pub struct ArrangementsIter<'a> {
+    arrangements: Arrangements<'a>,
+    next: u128,
+    count: u128,
 }
 
-pub fn day12_part_2(input: &str) -> u64 {
-    use std::sync::mpsc::{channel, Receiver, Sender};
-    use std::thread;
-
-    let thread_count = 16;
-    let mut result: u64 = 0;
-
-    let (transmit_results, receive_results) = channel::<u64>();
-    let mut transmit_work: Vec<Sender<String>> = Vec::new();
-    let mut receive_work: Vec<Receiver<String>> = Vec::new();
-
-    for _ in 0..thread_count {
-        let (tx, rx) = channel::<String>();
-        transmit_work.push(tx);
-        receive_work.push(rx);
-    }
-
-    for receiver in receive_work {
-        let transmit_results = transmit_results.clone();
-        thread::spawn(move || {
-            while let Ok(line) = receiver.recv() {
-                let mut cache: CacheMap = HashMap::new();
-                let split: Vec<_> = line.split(' ').collect();
-                let mut springs: String = String::new();
-                let mut records: String = String::new();
-                for i in 0..5 {
-                    springs += split[0];
-                    if i != 4 {
-                        springs += "?";
-                    }
-                    if !records.is_empty() {
-                        records += ",";
-                    }
-                    records += split[1];
-                }
+impl<'a> Iterator for ArrangementsIter<'a> {
+    type Item = String;
 
-                let records: Vec<RecordInt> = records
-                    .split(',')
-                    .map(|item| item.parse().expect("Records should be parseable."))
-                    .collect();
-
-                transmit_results
-                    .send(recurse_arrangements(
-                        springs.as_bytes(),
-                        &records,
-                        RecursionState::Continue,
-                        &mut cache,
-                    ))
-                    .expect("Results should be sent successfully.");
-            }
-        });
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.count {
+            return None;
+        }
+        let result = self.arrangements.nth(self.next);
+        self.next += 1;
+        result
     }
-    drop(transmit_results);
+}
 
-    for (index, line) in input.split('\n').enumerate() {
-        transmit_work[index % thread_count]
-            .send(line.to_owned())
-            .expect("Work should be sent successfully.");
-    }
+/// Sums the arrangements of every row after unfolding it `factor` times.
+pub fn day12_unfolded(input: &str, factor: usize) -> u128 {
+    let mut result: u128 = 0;
 
-    drop(transmit_work);
-    while let Ok(answer) = receive_results.recv() {
-        result += answer;
+    for line in input.split('\n') {
+        let (springs, records) = parse_line(line);
+        let (springs, records) = unfold(springs, &records, factor);
+        result += Arrangements::new(&springs, &records).count();
     }
 
     result
 }
 
+pub fn day12_part_1(input: &str) -> u64 {
+    day12_unfolded(input, 1)
+        .try_into()
+        .expect("Result should fit in a u64.")
+}
+
+pub fn day12_part_2(input: &str) -> u64 {
+    day12_unfolded(input, 5)
+        .try_into()
+        .expect("Result should fit in a u64.")
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::day12::{day12_part_1, day12_part_2};
+    use crate::day12::{day12_part_1, day12_part_2, day12_unfolded, parse_line, Arrangements};
 
     #[test]
     pub fn part1_example() {
@@ -238,4 +254,90 @@ mod tests {
             525152
         );
     }
+
+    #[test]
+    pub fn unfolded_factors() {
+        assert_eq!(day12_unfolded("???.### 1,1,3", 10), 1);
+        assert_eq!(day12_unfolded("????.#...#... 4,1,1", 10), 512);
+        assert_eq!(day12_unfolded(".??..??...?##. 1,1,3", 10), 536870912);
+        assert_eq!(day12_unfolded("?###???????? 3,2,1", 1), 10);
+        assert_eq!(
+            day12_unfolded("?###???????? 3,2,1", 20),
+            10 * 15u128.pow(19)
+        );
+    }
+
+    fn matches_records(arrangement: &str, records: &[u8]) -> bool {
+        let groups: Vec<u8> = arrangement
+            .split('.')
+            .filter(|group| !group.is_empty())
+            .map(|group| group.len() as u8)
+            .collect();
+        groups == records
+    }
+
+    #[test]
+    pub fn arrangements_enumerate() {
+        let (springs, records) = parse_line("?###???????? 3,2,1");
+        let all: Vec<String> = Arrangements::new(springs, &records).into_iter().collect();
+        assert_eq!(all.len(), 10);
+        assert_eq!(all[0], ".###.##.#...");
+        assert!(all.windows(2).all(|pair| pair[0] < pair[1]));
+        for arrangement in all.iter() {
+            assert!(matches_records(arrangement, &records));
+            for (a, b) in arrangement.bytes().zip(springs.iter()) {
+                assert!(*b == b'?' || a == *b);
+            }
+        }
+
+        let (springs, records) = parse_line(".??..??...?##. 1,1,3");
+        let all: Vec<String> = Arrangements::new(springs, &records).into_iter().collect();
+        assert_eq!(
+            all,
+            vec![
+                ".#...#....###.",
+                ".#....#...###.",
+                "..#..#....###.",
+                "..#...#...###.",
+            ]
+        );
+
+        let (springs, records) = parse_line("#.# 2");
+        assert_eq!(Arrangements::new(springs, &records).into_iter().count(), 0);
+    }
+
+    #[test]
+    pub fn arrangements_nth_and_sample() {
+        let (springs, records) = parse_line("????.######..#####. 1,6,5");
+        let arrangements = Arrangements::new(springs, &records);
+        assert_eq!(arrangements.count(), 4);
+        assert_eq!(arrangements.nth(0), Some("#....######..#####.".to_owned()));
+        assert_eq!(arrangements.nth(3), Some("...#.######..#####.".to_owned()));
+        assert_eq!(arrangements.nth(4), None);
+
+        let mut state: u64 = 0x2545F4914F6CDD1D;
+        let mut xorshift = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state
+        };
+        let mut seen = [0; 4];
+        for _ in 0..400 {
+            let sample = arrangements
+                .sample(&mut xorshift)
+                .expect("Row should have arrangements.");
+            let index = (0..4)
+                .find(|&index| arrangements.nth(index).as_ref() == Some(&sample))
+                .expect("Sample should be a valid arrangement.");
+            seen[index as usize] += 1;
+        }
+        assert!(seen.iter().all(|&count| count > 50));
+
+        let (springs, records) = parse_line("### 1");
+        assert_eq!(
+            Arrangements::new(springs, &records).sample(&mut xorshift),
+            None
+        );
+    }
 }
@@ -1,88 +1,142 @@
-fn row_diffs(lines: &[&[u8]], row_a: usize, row_b: usize) -> usize {
-    let mut diffs = 0;
+/// Which way a line of reflection runs.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    /// Between two rows.
+    Horizontal,
+    /// Between two columns.
+    Vertical,
+}
+use Orientation::*;
+
+/// A line of reflection, `axis` rows above or columns left of it, and the `(row, col)`
+/// of each smudge that must be fixed for it to be a perfect reflection. Each smudge is
+/// reported on the top or left side of the line.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Reflection {
+    pub orientation: Orientation,
+    pub axis: usize,
+    pub smudges: Vec<(usize, usize)>,
+}
 
-    for col in 0..lines[0].len() {
-        if lines[row_a][col] != lines[row_b][col] {
-            diffs += 1;
+impl Reflection {
+    pub fn score(&self) -> usize {
+        match self.orientation {
+            Horizontal => self.axis * 100,
+            Vertical => self.axis,
         }
     }
+}
 
-    diffs
+/// A pattern with each row and each column packed into bits, set where there is a rock.
+/// Bit `i` of a line is bit `i % 64` of word `i / 64`.
+pub struct Pattern {
+    rows: Vec<Vec<u64>>,
+    cols: Vec<Vec<u64>>,
 }
 
-fn has_horizontal_symmetry(lines: &[&[u8]], diffs_expected: usize) -> Option<usize> {
-    'outer: for bottom_row in (1..lines.len()).rev() {
-        let top_row = bottom_row - 1;
-        let max_movement = top_row.min(lines.len() - bottom_row - 1);
-        let mut diffs = 0;
-        for i in 0..=max_movement {
-            diffs += row_diffs(lines, top_row - i, bottom_row + i);
-            if diffs > diffs_expected {
-                continue 'outer;
+impl Pattern {
+    pub fn parse(input: &str) -> Pattern {
+        let lines: Vec<&[u8]> = input.split('\n').map(|line| line.as_bytes()).collect();
+        let width = lines[0].len();
+
+        let mut rows = vec![vec![0; width.div_ceil(64)]; lines.len()];
+        let mut cols = vec![vec![0; lines.len().div_ceil(64)]; width];
+        for (row, line) in lines.iter().enumerate() {
+            for (col, &char) in line.iter().enumerate() {
+                if char == b'#' {
+                    rows[row][col / 64] |= 1 << (col % 64);
+                    cols[col][row / 64] |= 1 << (row % 64);
+                }
             }
         }
 
-        if diffs == diffs_expected {
-            return Some(bottom_row);
-        }
+        Pattern { rows, cols }
     }
 
-    None
+    /// Every line of reflection that needs exactly `smudges` cells fixing. Horizontal
+    /// lines come first, then vertical ones, each starting from the bottom or right.
+    pub fn reflections(&self, smudges: usize) -> Vec<Reflection> {
+        let mut result = reflections(&self.rows, smudges, Horizontal, |row, col| (row, col));
+        result.extend(reflections(&self.cols, smudges, Vertical, |col, row| {
+            (row, col)
+        }));
+        result
+    }
 }
 
-pub fn transform(lines: &[&[u8]]) -> Vec<Vec<u8>> {
-    let mut result: Vec<Vec<u8>> = Vec::new();
-    for row in 0..lines[0].len() {
-        let mut row_result: Vec<u8> = Vec::new();
-        for line in lines {
-            row_result.push(line[row]);
+/// Every line between two of `lines` where they mirror each other apart from exactly
+/// `smudges` bits. `cell` gives the `(row, col)` of a bit in one of the lines.
+fn reflections(
+    lines: &[Vec<u64>],
+    smudges: usize,
+    orientation: Orientation,
+    cell: impl Fn(usize, usize) -> (usize, usize),
+) -> Vec<Reflection> {
+    let mut result = Vec::new();
+
+    'outer: for axis in (1..lines.len()).rev() {
+        let mut diffs = 0;
+        for (before, after) in (0..axis).rev().zip(axis..lines.len()) {
+            diffs += lines[before]
+                .iter()
+                .zip(lines[after].iter())
+                .map(|(a, b)| (a ^ b).count_ones() as usize)
+                .sum::<usize>();
+            if diffs > smudges {
+                continue 'outer;
+            }
         }
-        result.push(row_result);
-    }
-    result
-}
 
-pub fn day13_part_1(input: &str) -> usize {
-    let mut result: usize = 0;
-
-    for group in input.split("\n\n") {
-        let lines: Vec<_> = group.split('\n').map(|str| str.as_bytes()).collect();
-        if let Some(rows_above) = has_horizontal_symmetry(lines.as_slice(), 0) {
-            result += rows_above * 100;
-        } else {
-            let transformed = transform(lines.as_slice());
-            let transformed: Vec<_> = transformed.iter().map(|item| item.as_slice()).collect();
-            if let Some(cols_left) = has_horizontal_symmetry(transformed.as_slice(), 0) {
-                result += cols_left;
+        if diffs == smudges {
+            let mut cells = Vec::new();
+            for (before, after) in (0..axis).rev().zip(axis..lines.len()) {
+                for (word, (a, b)) in lines[before].iter().zip(lines[after].iter()).enumerate() {
+                    let mut diff = a ^ b;
+                    while diff != 0 {
+                        cells.push(cell(before, word * 64 + diff.trailing_zeros() as usize));
+                        diff &= diff - 1;
+                    }
+                }
             }
+            cells.sort();
+            result.push(Reflection {
+                orientation,
+                axis,
+                smudges: cells,
+            });
         }
     }
 
     result
 }
 
-pub fn day13_part_2(input: &str) -> usize {
-    let mut result: usize = 0;
-
-    for group in input.split("\n\n") {
-        let lines: Vec<_> = group.split('\n').map(|str| str.as_bytes()).collect();
-        if let Some(rows_above) = has_horizontal_symmetry(lines.as_slice(), 1) {
-            result += rows_above * 100;
-        } else {
-            let transformed = transform(lines.as_slice());
-            let transformed: Vec<_> = transformed.iter().map(|item| item.as_slice()).collect();
-            if let Some(cols_left) = has_horizontal_symmetry(transformed.as_slice(), 1) {
-                result += cols_left;
-            }
-        }
-    }
+/// The reflections of each pattern in `input` that need exactly `smudges` cells fixing.
+pub fn day13_reflections(input: &str, smudges: usize) -> Vec<Vec<Reflection>> {
+    input
+        .split("\n\n")
+        .map(|group| Pattern::parse(group).reflections(smudges))
+        .collect()
+}
 
-    result
+fn summarise(input: &str, smudges: usize) -> usize {
+    day13_reflections(input, smudges)
+        .iter()
+        .filter_map(|reflections| reflections.first())
+        .map(Reflection::score)
+        .sum()
+}
+
+pub fn day13_part_1(input: &str) -> usize {
+    summarise(input, 0)
+}
+
+pub fn day13_part_2(input: &str) -> usize {
+    summarise(input, 1)
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day13::{day13_part_1, day13_part_2};
+    use crate::day13::{day13_part_1, day13_part_2, day13_reflections, Orientation::*, Reflection};
 
     #[test]
     pub fn part1_example() {
@@ -183,4 +237,100 @@ mod tests {
             400
         );
     }
+
+    #[test]
+    pub fn smudge_locations() {
+        let reflections = day13_reflections(
+            "#.##..##.
+..#.##.#.
+##......#
+##......#
+..#.##.#.
+..##..##.
+#.#.##.#.
+
+#...##..#
+#....#..#
+..##..###
+#####.##.
+#####.##.
+..##..###
+#....#..#",
+            1,
+        );
+        assert_eq!(
+            reflections,
+            [
+                vec![Reflection {
+                    orientation: Horizontal,
+                    axis: 3,
+                    smudges: vec![(0, 0)],
+                }],
+                vec![Reflection {
+                    orientation: Horizontal,
+                    axis: 1,
+                    smudges: vec![(0, 4)],
+                }],
+            ]
+        );
+
+        // Every row and column of a plain pattern reflects perfectly, and a single rock
+        // is a smudge for the lines it has no partner across:
+        let plain = day13_reflections("...\n...", 0);
+        assert_eq!(plain[0].len(), 3);
+        let rock = day13_reflections("#..\n...", 1);
+        assert_eq!(
+            rock[0],
+            [
+                Reflection {
+                    orientation: Horizontal,
+                    axis: 1,
+                    smudges: vec![(0, 0)],
+                },
+                Reflection {
+                    orientation: Vertical,
+                    axis: 1,
+                    smudges: vec![(0, 0)],
+                },
+            ]
+        );
+
+        // Two smudges either side of a vertical line:
+        assert_eq!(
+            day13_reflections("#..#\n.#..\n#..#", 2)[0],
+            [Reflection {
+                orientation: Vertical,
+                axis: 3,
+                smudges: vec![(0, 2), (2, 2)],
+            }]
+        );
+    }
+
+    #[test]
+    pub fn large_patterns() {
+        // A 70 column pattern, mirrored about the line after column 35, with a smudge in
+        // the last column:
+        let half = "#..##.#...#.##..#..#....##.#.#..#.#";
+        let reversed: String = half.chars().rev().collect();
+        let row = format!("{}{}", half, reversed);
+        let smudged = format!("{}.", &row[..69]);
+        let pattern = format!("{}\n{}\n{}", row, smudged, row);
+
+        let reflections = day13_reflections(&pattern, 1);
+        assert!(reflections[0].contains(&Reflection {
+            orientation: Vertical,
+            axis: 35,
+            smudges: vec![(1, 0)],
+        }));
+        assert!(!day13_reflections(&pattern, 0)[0]
+            .iter()
+            .any(|reflection| reflection.axis == 35));
+
+        // Every pair of rows reflects, and the bottom-most line is scored as before:
+        let tall = vec!["#.#."; 70].join("\n");
+        let reflections = day13_reflections(&tall, 0);
+        assert_eq!(reflections[0].len(), 69);
+        assert_eq!(reflections[0][0].axis, 69);
+        assert_eq!(day13_part_1(&tall), 6900);
+    }
 }
@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use crate::cycle;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Pos {
@@ -14,7 +14,7 @@ enum Rock {
 }
 use Rock::*;
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 struct Grid {
     arr: Vec<Rock>,
     rows: usize,
@@ -114,42 +114,8 @@ impl Grid {
         }
     }
 
-    fn tilt_four(&mut self, max: Pos) {
-        self.tilt_all(Pos { row: -1, col: 0 }, max);
-        self.tilt_all(Pos { row: 0, col: -1 }, max);
-        self.tilt_all(Pos { row: 1, col: 0 }, max);
-        self.tilt_all(Pos { row: 0, col: 1 }, max);
-    }
-
-    fn tilt_one_billion(&mut self, max: Pos) {
-        let mut seen: HashSet<Vec<Rock>> = HashSet::new();
-        let max_iters = 1000000000;
-        let mut i: usize = 0;
-        let mut cycle_start: Option<(usize, Vec<Rock>)> = None;
-        let mut waiting_for_end = false;
-        while i < max_iters {
-            self.tilt_four(max);
-
-            if seen.contains(&self.arr) {
-                if !waiting_for_end {
-                    if cycle_start.is_none() {
-                        cycle_start = Some((i, self.arr.clone()));
-                    } else if let Some((cycle_start_i, cycle_start_vec)) = &cycle_start {
-                        if self.arr == *cycle_start_vec {
-                            let cycle_size = i - cycle_start_i;
-                            let remaining = max_iters - i;
-                            let to_add = (remaining / cycle_size) * cycle_size;
-                            i += to_add;
-                            waiting_for_end = true;
-                        }
-                    }
-                }
-            } else {
-                seen.insert(self.arr.clone());
-            }
-
-            i += 1;
-        }
+    fn score(&self) -> i64 {
+        self.load(North) as i64
     }
 
     fn max(&self) -> Pos {
@@ -159,38 +125,365 @@ impl Grid {
         }
     }
 
-    fn score(&self) -> i64 {
-        self.arr.iter().enumerate().fold(0, |accum, (index, rock)| {
-            if *rock == Circle {
-                let row = index / self.rows;
-                accum + ((self.rows as i64) - row as i64)
-            } else {
-                accum
+    fn to_text(&self) -> String {
+        self.arr
+            .chunks(self.cols)
+            .map(|row| {
+                row.iter()
+                    .map(|rock| match rock {
+                        Square => '#',
+                        Circle => 'O',
+                        Nothing => '.',
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+impl Platform for Grid {
+    fn parse(input: &str) -> Grid {
+        Grid::new(input)
+    }
+
+    fn tilt(&mut self, direction: Direction) {
+        let max = self.max();
+        self.tilt_all(direction.offset(), max);
+    }
+
+    fn round_rocks(&self) -> Vec<(usize, usize)> {
+        (0..self.arr.len())
+            .filter(|&index| self.arr[index] == Circle)
+            .map(|index| (index / self.cols, index % self.cols))
+            .collect()
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    fn render(&self) -> String {
+        self.to_text()
+    }
+}
+
+/// The same platform as `Grid`, but with each row packed into bits so that a whole run
+/// of rocks can be moved at once.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PackedGrid {
+    /// `words` per row, with column `col` at bit `col % 64` of word `col / 64`.
+    round: Vec<u64>,
+    square: Vec<u64>,
+    /// The `(row, start, end)` columns of each gap between square rocks in a row.
+    runs: Vec<(usize, usize, usize)>,
+    /// The `(col, start, end)` rows of each gap between square rocks in a column.
+    column_runs: Vec<(usize, usize, usize)>,
+    rows: usize,
+    cols: usize,
+    words: usize,
+}
+
+impl std::hash::Hash for PackedGrid {
+    /// Only the round rocks ever move.
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.round.hash(state);
+    }
+}
+
+/// The bits of `word` for columns `start..end`.
+fn column_mask(word: usize, start: usize, end: usize) -> u64 {
+    let low = start.saturating_sub(word * 64).min(64);
+    let high = end.saturating_sub(word * 64).min(64);
+    let below = |bits: usize| {
+        if bits == 64 {
+            u64::MAX
+        } else {
+            (1 << bits) - 1
+        }
+    };
+    below(high) & !below(low)
+}
+
+/// The positions of the set bits in `bits`, which holds lines of `words` words each, as
+/// `(line, bit)` pairs.
+fn set_bits(bits: &[u64], words: usize) -> impl Iterator<Item = (usize, usize)> + '_ {
+    bits.iter().enumerate().flat_map(move |(index, &word)| {
+        let mut remaining = word;
+        std::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
             }
+            let bit = remaining.trailing_zeros() as usize;
+            remaining &= remaining - 1;
+            Some((index / words, (index % words) * 64 + bit))
         })
+    })
+}
+
+/// Counts the rocks in each `(line, start, end)` run of `bits` and packs them against the
+/// start of the run, or the end if `towards_start` is false.
+fn pack_runs(bits: &mut [u64], words: usize, runs: &[(usize, usize, usize)], towards_start: bool) {
+    for &(line, start, end) in runs.iter() {
+        let line = &mut bits[line * words..(line + 1) * words];
+        let (first, last) = (start / 64, (end - 1) / 64);
+        let mut count = 0;
+        for (word, bits) in line.iter_mut().enumerate().take(last + 1).skip(first) {
+            let mask = column_mask(word, start, end);
+            count += (*bits & mask).count_ones() as usize;
+            *bits &= !mask;
+        }
+
+        let (start, end) = if towards_start {
+            (start, start + count)
+        } else {
+            (end - count, end)
+        };
+        for (word, bits) in line.iter_mut().enumerate().take(last + 1).skip(first) {
+            *bits |= column_mask(word, start, end);
+        }
     }
+}
 
-    #[allow(dead_code)]
-    fn print(&self) {
-        let max = self.max();
+impl PackedGrid {
+    /// Packs the round rocks a column at a time, by copying them into columns of bits
+    /// and back again.
+    fn tilt_vertical(&mut self, direction: Direction) {
+        let column_words = self.rows.div_ceil(64);
+        let mut columns = vec![0; self.cols * column_words];
+        for (row, col) in set_bits(&self.round, self.words) {
+            columns[col * column_words + row / 64] |= 1 << (row % 64);
+        }
 
-        for row in 0..=max.row {
-            for col in 0..=max.col {
-                print!(
-                    "{}",
-                    match self.arr[self.pos_to_index(Pos { row, col })] {
-                        Square => '#',
-                        Circle => 'O',
-                        Nothing => '.',
+        pack_runs(
+            &mut columns,
+            column_words,
+            &self.column_runs,
+            direction == North,
+        );
+
+        self.round.fill(0);
+        for (col, row) in set_bits(&columns, column_words) {
+            self.round[row * self.words + col / 64] |= 1 << (col % 64);
+        }
+    }
+
+    fn tilt_horizontal(&mut self, direction: Direction) {
+        pack_runs(&mut self.round, self.words, &self.runs, direction == West);
+    }
+}
+
+impl Platform for PackedGrid {
+    fn parse(input: &str) -> PackedGrid {
+        let lines: Vec<&str> = input.split('\n').collect();
+        let rows = lines.len();
+        let cols = lines[0].len();
+        let words = cols.div_ceil(64);
+        let mut round = vec![0; rows * words];
+        let mut square = vec![0; rows * words];
+
+        for (row, line) in lines.iter().enumerate() {
+            for (col, char) in line.chars().enumerate() {
+                let (index, bit) = (row * words + col / 64, 1 << (col % 64));
+                match char {
+                    '.' => {}
+                    '#' => square[index] |= bit,
+                    'O' => round[index] |= bit,
+                    _ => unreachable!("Encountered unexpected character {}", char),
+                }
+            }
+        }
+
+        let mut runs = Vec::new();
+        for (row, line) in lines.iter().enumerate() {
+            let mut start = 0;
+            for (col, char) in line.chars().chain(std::iter::once('#')).enumerate() {
+                if char == '#' {
+                    if start < col {
+                        runs.push((row, start, col));
                     }
-                );
+                    start = col + 1;
+                }
             }
-            println!();
         }
-        println!();
+
+        let mut column_runs = Vec::new();
+        for col in 0..cols {
+            let mut start = 0;
+            for row in 0..=rows {
+                if row == rows || square[row * words + col / 64] & (1 << (col % 64)) != 0 {
+                    if start < row {
+                        column_runs.push((col, start, row));
+                    }
+                    start = row + 1;
+                }
+            }
+        }
+
+        PackedGrid {
+            round,
+            square,
+            runs,
+            column_runs,
+            rows,
+            cols,
+            words,
+        }
+    }
+
+    fn tilt(&mut self, direction: Direction) {
+        match direction {
+            North | South => self.tilt_vertical(direction),
+            West | East => self.tilt_horizontal(direction),
+        }
+    }
+
+    fn round_rocks(&self) -> Vec<(usize, usize)> {
+        let mut result = Vec::new();
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                if self.round[row * self.words + col / 64] & (1 << (col % 64)) != 0 {
+                    result.push((row, col));
+                }
+            }
+        }
+        result
+    }
+
+    fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    fn render(&self) -> String {
+        (0..self.rows)
+            .map(|row| {
+                (0..self.cols)
+                    .map(|col| {
+                        let (index, bit) = (row * self.words + col / 64, 1 << (col % 64));
+                        if self.round[index] & bit != 0 {
+                            'O'
+                        } else if self.square[index] & bit != 0 {
+                            '#'
+                        } else {
+                            '.'
+                        }
+                    })
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// The way a platform is tilted, and the edge its rocks roll towards.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Direction {
+    North,
+    West,
+    South,
+    East,
+}
+use Direction::*;
+
+impl Direction {
+    fn offset(&self) -> Pos {
+        match self {
+            North => Pos { row: -1, col: 0 },
+            West => Pos { row: 0, col: -1 },
+            South => Pos { row: 1, col: 0 },
+            East => Pos { row: 0, col: 1 },
+        }
     }
 }
 
+/// One spin cycle.
+pub const SPIN: [Direction; 4] = [North, West, South, East];
+
+/// Parses tilts like `NWSE`.
+pub fn parse_tilts(tilts: &str) -> Vec<Direction> {
+    tilts
+        .chars()
+        .map(|char| match char {
+            'N' => North,
+            'W' => West,
+            'S' => South,
+            'E' => East,
+            unknown => panic!("Expected a tilt of N, W, S or E, got: {}", unknown),
+        })
+        .collect()
+}
+
+/// A platform of rocks that can be tilted, whatever its representation.
+pub trait Platform: Clone + Eq + std::hash::Hash + Sized {
+    fn parse(input: &str) -> Self;
+
+    /// Rolls every round rock as far as it will go towards `direction`.
+    fn tilt(&mut self, direction: Direction);
+
+    /// The `(row, col)` of every round rock.
+    fn round_rocks(&self) -> Vec<(usize, usize)>;
+
+    /// The number of rows and columns.
+    fn size(&self) -> (usize, usize);
+
+    /// The platform drawn as in the puzzle input.
+    fn render(&self) -> String;
+
+    fn tilt_sequence(&mut self, tilts: &[Direction]) {
+        for &direction in tilts {
+            self.tilt(direction);
+        }
+    }
+
+    /// The load on the `edge` support beams. Each round rock adds the number of rows or
+    /// columns from the opposite edge up to and including its own.
+    fn load(&self, edge: Direction) -> usize {
+        let (rows, cols) = self.size();
+        self.round_rocks()
+            .into_iter()
+            .map(|(row, col)| match edge {
+                North => rows - row,
+                West => cols - col,
+                South => row + 1,
+                East => col + 1,
+            })
+            .sum()
+    }
+
+    /// The platform after applying `tilts` `iterations` times, skipping ahead once the
+    /// platform starts repeating.
+    fn after(&self, tilts: &[Direction], iterations: usize) -> cycle::CycleResult<Self> {
+        cycle::hash_indexed(
+            self.clone(),
+            |platform| {
+                let mut next = platform.clone();
+                next.tilt_sequence(tilts);
+                next
+            },
+            iterations,
+        )
+    }
+
+    /// The load on `edge` before and after each of `iterations` applications of `tilts`.
+    fn load_trace(&self, tilts: &[Direction], edge: Direction, iterations: usize) -> Vec<usize> {
+        let mut platform = self.clone();
+        let mut result = vec![platform.load(edge)];
+        for _ in 0..iterations {
+            platform.tilt_sequence(tilts);
+            result.push(platform.load(edge));
+        }
+        result
+    }
+}
+
+/// The load on `edge` after applying `tilts`, such as `NWSE`, `iterations` times.
+pub fn day14_load_after(input: &str, tilts: &str, iterations: usize, edge: Direction) -> usize {
+    PackedGrid::parse(input)
+        .after(&parse_tilts(tilts), iterations)
+        .state
+        .load(edge)
+}
+
 pub fn day14_part_1(input: &str) -> i64 {
     let mut grid = Grid::new(input);
     let max = grid.max();
@@ -199,17 +492,15 @@ pub fn day14_part_1(input: &str) -> i64 {
 }
 
 pub fn day14_part_2(input: &str) -> i64 {
-    let mut grid = Grid::new(input);
-    let max = grid.max();
-    grid.tilt_one_billion(max);
-    grid.score()
+    day14_load_after(input, "NWSE", 1000000000, North) as i64
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day14::day14_part_1;
+    use crate::cycle::{self, Cycle};
+    use crate::day14::{day14_load_after, day14_part_1, parse_tilts, Direction::*, Platform, SPIN};
 
-    use super::Grid;
+    use super::{Grid, PackedGrid};
 
     #[test]
     pub fn part1_example() {
@@ -244,8 +535,7 @@ O.#..O.#.#
 #....###..
 #OO..#....",
         );
-        let max = grid.max();
-        grid.tilt_four(max);
+        grid.tilt_sequence(&SPIN);
         assert_eq!(
             grid,
             Grid::new(
@@ -262,7 +552,7 @@ O.#..O.#.#
             )
         );
 
-        grid.tilt_four(max);
+        grid.tilt_sequence(&SPIN);
         assert_eq!(
             grid,
             Grid::new(
@@ -279,7 +569,7 @@ O.#..O.#.#
             )
         );
 
-        grid.tilt_four(max);
+        grid.tilt_sequence(&SPIN);
         assert_eq!(
             grid,
             Grid::new(
@@ -308,8 +598,128 @@ O.#..O.#.#
 #....###..
 #OO..#....",
         );
-        grid.tilt_one_billion(max);
+        assert_eq!(grid.after(&SPIN, 1000000000).state.score(), 64);
+    }
 
-        assert_eq!(grid.score(), 64);
+    #[test]
+    pub fn spin_cycle_detection() {
+        let grid = Grid::new(
+            "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....",
+        );
+        let spin = |grid: &Grid| {
+            let mut next = grid.clone();
+            next.tilt_sequence(&SPIN);
+            next
+        };
+        let expected_cycle = Some(Cycle {
+            offset: 3,
+            length: 7,
+        });
+        assert_eq!(
+            cycle::brent(grid.clone(), spin, 1000000000).cycle,
+            expected_cycle
+        );
+        assert_eq!(cycle::floyd(grid, spin, 1000000000).cycle, expected_cycle);
+    }
+
+    #[test]
+    pub fn tilt_sequences() {
+        let input = "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....";
+        let grid = Grid::parse(input);
+        let packed = PackedGrid::parse(input);
+        assert_eq!(packed.render(), input);
+        assert_eq!(grid.load(North), packed.load(North));
+
+        for tilts in ["N", "W", "S", "E", "NNESW", "NWSE", "EESWNWN"] {
+            let tilts = parse_tilts(tilts);
+            let (mut grid, mut packed) = (grid.clone(), packed.clone());
+            grid.tilt_sequence(&tilts);
+            packed.tilt_sequence(&tilts);
+            assert_eq!(grid.render(), packed.render());
+            for edge in [North, West, South, East] {
+                assert_eq!(grid.load(edge), packed.load(edge));
+            }
+        }
+
+        // A single rock in the top left corner of a 2x3 platform:
+        let corner = PackedGrid::parse("O..\n...");
+        assert_eq!(
+            [North, West, South, East].map(|edge| corner.load(edge)),
+            [2, 3, 1, 1]
+        );
+
+        assert_eq!(day14_load_after(input, "NWSE", 1000000000, North), 64);
+        assert_eq!(day14_load_after(input, "N", 1, North), 136);
+    }
+
+    #[test]
+    pub fn load_traces() {
+        let grid = Grid::parse(
+            "O....#....
+O.OO#....#
+.....##...
+OO.#O....O
+.O.....O#.
+O.#..O.#.#
+..O..#O..O
+.......O..
+#....###..
+#OO..#....",
+        );
+        let trace = grid.load_trace(&SPIN, North, 20);
+        assert_eq!(trace.len(), 21);
+        assert_eq!(trace[0], 104);
+        assert_eq!(trace[1], 87);
+        // The spin cycle repeats every 7 iterations from the third:
+        for iteration in 3..14 {
+            assert_eq!(trace[iteration], trace[iteration + 7]);
+        }
+    }
+
+    #[test]
+    pub fn wide_packed_grids() {
+        // Rows span several words, so rocks must cross word boundaries:
+        let row = format!("{}O{}#{}O", ".".repeat(70), ".".repeat(60), ".".repeat(5));
+        let input = format!("{}\n{}", row, row.replace('O', "."));
+        let mut grid = Grid::parse(&input);
+        let mut packed = PackedGrid::parse(&input);
+        assert_eq!(packed.render(), input);
+
+        for tilts in ["W", "E", "S", "N", "ESWN"] {
+            let tilts = parse_tilts(tilts);
+            grid.tilt_sequence(&tilts);
+            packed.tilt_sequence(&tilts);
+            assert_eq!(grid.render(), packed.render());
+        }
+
+        // And the same turned on its side, so columns span several words too:
+        let tall: Vec<String> = row.chars().map(|char| format!("{}.", char)).collect();
+        let input = tall.join("\n");
+        let mut grid = Grid::parse(&input);
+        let mut packed = PackedGrid::parse(&input);
+        for tilts in ["N", "S", "E", "W", "NWSE"] {
+            let tilts = parse_tilts(tilts);
+            grid.tilt_sequence(&tilts);
+            packed.tilt_sequence(&tilts);
+            assert_eq!(grid.render(), packed.render());
+        }
     }
 }
@@ -1,72 +1,49 @@
-fn hash(input: &str) -> usize {
-    let mut result: usize = 0;
+use std::hash::{BuildHasher, Hasher};
 
-    for char in input.bytes() {
-        result += char as usize;
-        result *= 17;
-        result %= 256;
-    }
+use crate::bucket_map::BucketMap;
 
-    result
+/// The Holiday ASCII String Helper algorithm.
+#[derive(Default)]
+pub struct HolidayHasher {
+    state: u8,
 }
 
-struct HashMap<'a> {
-    slots: [Vec<(&'a str, u8)>; 256],
-}
-
-impl<'a> HashMap<'a> {
-    fn new() -> HashMap<'a> {
-        HashMap {
-            slots: core::array::from_fn(|_| Vec::new()),
+impl Hasher for HolidayHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.state = self.state.wrapping_add(byte).wrapping_mul(17);
         }
     }
 
-    fn insert(&mut self, label: &'a str, value: u8) {
-        let index = hash(label);
-        for (other_label, other_value) in self.slots[index].iter_mut() {
-            if *other_label == label {
-                *other_value = value;
-                return;
-            }
-        }
-
-        self.slots[index].push((label, value));
+    fn finish(&self) -> u64 {
+        self.state as u64
     }
+}
 
-    fn remove(&mut self, label: &str) {
-        let slot_index = hash(label);
-        let mut vec_index: Option<usize> = None;
-        for (index, (other_label, _)) in self.slots[slot_index].iter().enumerate() {
-            if *other_label == label {
-                vec_index = Some(index);
-                break;
-            }
-        }
+#[derive(Clone, Default)]
+pub struct BuildHolidayHasher;
 
-        if let Some(vec_index) = vec_index {
-            self.slots[slot_index].remove(vec_index);
-        }
+impl BuildHasher for BuildHolidayHasher {
+    type Hasher = HolidayHasher;
+    fn build_hasher(&self) -> Self::Hasher {
+        HolidayHasher::default()
     }
+}
 
-    fn focusing_power(&self) -> usize {
-        let mut result: usize = 0;
-        for (index, vec) in self.slots.iter().enumerate() {
-            for (lens_index, (_label, lens)) in vec.iter().enumerate() {
-                result += (index + 1) * (lens_index + 1) * (*lens as usize);
-            }
-        }
-        result
-    }
+fn hash(input: &str) -> usize {
+    let mut hasher = HolidayHasher::default();
+    hasher.write(input.as_bytes());
+    hasher.finish() as usize
+}
 
-    #[allow(dead_code)]
-    fn print(&self) {
-        for (i, vec) in self.slots.iter().enumerate() {
-            if !vec.is_empty() {
-                println!("Box {}: {:?}", i, vec);
-            }
+fn focusing_power(map: &BucketMap<String, u8, BuildHolidayHasher>) -> usize {
+    let mut result: usize = 0;
+    for (index, lenses) in map.buckets().enumerate() {
+        for (lens_index, (_label, lens)) in lenses.enumerate() {
+            result += (index + 1) * (lens_index + 1) * (*lens as usize);
         }
-        println!();
     }
+    result
 }
 
 pub fn day15_part_1(input: &str) -> usize {
@@ -74,11 +51,11 @@ pub fn day15_part_1(input: &str) -> usize {
 }
 
 pub fn day15_part_2(input: &str) -> usize {
-    let mut map = HashMap::new();
+    let mut map = BucketMap::with_hasher(256, BuildHolidayHasher);
 
     for command in input.split(',') {
-        if command.ends_with('-') {
-            map.remove(&command[0..command.len() - 1]);
+        if let Some(label) = command.strip_suffix('-') {
+            map.remove(label);
         } else {
             let mut pieces = command.split('=');
             let label = pieces.next().expect("Label should exist.");
@@ -87,11 +64,11 @@ pub fn day15_part_2(input: &str) -> usize {
                 .expect("Value should exist.")
                 .parse()
                 .expect("Value should be a valid u8.");
-            map.insert(label, value);
+            map.insert(label.to_owned(), value);
         }
     }
 
-    map.focusing_power()
+    focusing_power(&map)
 }
 
 #[cfg(test)]
@@ -1,16 +1,370 @@
+use crate::graph;
+use std::collections::HashMap;
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
-enum Mirror {
-    Empty,
+pub enum Direction {
+    Down,
+    Up,
     Right,
     Left,
-    Horizontal,
-    Vertical,
 }
 
-use Mirror::*;
+use Direction::*;
+
+const DIRECTIONS: [Direction; 4] = [Down, Up, Right, Left];
+
+impl Direction {
+    fn offset(&self) -> Pos {
+        match self {
+            Down => Pos { row: 1, col: 0 },
+            Up => Pos { row: -1, col: 0 },
+            Right => Pos { row: 0, col: 1 },
+            Left => Pos { row: 0, col: -1 },
+        }
+    }
+}
+
+/// The directions a beam leaves a tile in, given the direction it entered in.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TileBehaviour {
+    /// Indexed by `direction_index` of the entering beam.
+    outputs: [Vec<Direction>; 4],
+}
+
+impl TileBehaviour {
+    pub fn from_fn(outputs: impl Fn(Direction) -> Vec<Direction>) -> TileBehaviour {
+        TileBehaviour {
+            outputs: DIRECTIONS.map(outputs),
+        }
+    }
+
+    pub fn empty() -> TileBehaviour {
+        TileBehaviour::from_fn(|direction| vec![direction])
+    }
+
+    pub fn absorber() -> TileBehaviour {
+        TileBehaviour::from_fn(|_| Vec::new())
+    }
+
+    pub fn forward_mirror() -> TileBehaviour {
+        TileBehaviour::from_fn(|direction| {
+            vec![match direction {
+                Down => Left,
+                Up => Right,
+                Right => Up,
+                Left => Down,
+            }]
+        })
+    }
+
+    pub fn back_mirror() -> TileBehaviour {
+        TileBehaviour::from_fn(|direction| {
+            vec![match direction {
+                Down => Right,
+                Up => Left,
+                Right => Down,
+                Left => Up,
+            }]
+        })
+    }
+
+    pub fn horizontal_splitter() -> TileBehaviour {
+        TileBehaviour::from_fn(|direction| match direction {
+            Down | Up => vec![Left, Right],
+            _ => vec![direction],
+        })
+    }
+
+    pub fn vertical_splitter() -> TileBehaviour {
+        TileBehaviour::from_fn(|direction| match direction {
+            Left | Right => vec![Up, Down],
+            _ => vec![direction],
+        })
+    }
+
+    /// Sends beams out in every direction but back the way they came.
+    pub fn four_way_splitter() -> TileBehaviour {
+        TileBehaviour::from_fn(|direction| {
+            DIRECTIONS
+                .into_iter()
+                .filter(|&other| other.offset() != direction.offset().reversed())
+                .collect()
+        })
+    }
+
+    /// Changes what happens to beams entering in `direction`.
+    pub fn with_output(mut self, direction: Direction, outputs: &[Direction]) -> TileBehaviour {
+        self.outputs[direction_index(direction.offset())] = outputs.to_vec();
+        self
+    }
+
+    fn outputs(&self, direction: Pos) -> &[Direction] {
+        &self.outputs[direction_index(direction)]
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == TileBehaviour::empty()
+    }
+}
+
+/// The tiles a contraption is built from, by character.
+#[derive(Debug, Clone)]
+pub struct Optics {
+    tiles: Vec<(char, TileBehaviour)>,
+    /// A beam entering the first `(row, col)` continues from the second.
+    portals: Vec<((usize, usize), (usize, usize))>,
+}
+
+impl Optics {
+    pub fn standard() -> Optics {
+        Optics {
+            tiles: Vec::new(),
+            portals: Vec::new(),
+        }
+        .with_tile('.', TileBehaviour::empty())
+        .with_tile('/', TileBehaviour::forward_mirror())
+        .with_tile('\\', TileBehaviour::back_mirror())
+        .with_tile('-', TileBehaviour::horizontal_splitter())
+        .with_tile('|', TileBehaviour::vertical_splitter())
+    }
+
+    pub fn with_tile(mut self, symbol: char, behaviour: TileBehaviour) -> Optics {
+        match self.tiles.iter_mut().find(|(other, _)| *other == symbol) {
+            Some(tile) => tile.1 = behaviour,
+            None => self.tiles.push((symbol, behaviour)),
+        }
+        self
+    }
+
+    /// Beams entering the tile at `from` carry on from the tile at `to`.
+    pub fn with_portal(mut self, from: (usize, usize), to: (usize, usize)) -> Optics {
+        self.portals.push((from, to));
+        self
+    }
+
+    /// Adds to the standard optics from a config with one declaration per line, like:
+    ///
+    /// ```text
+    /// '#' = absorber
+    /// '>' = forward_mirror, left -> left
+    /// portal 1,5 -> 3,0
+    /// ```
+    pub fn parse(config: &str) -> Optics {
+        let mut optics = Optics::standard();
+
+        for line in config
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+        {
+            if let Some(portal) = line.strip_prefix("portal ") {
+                let (from, to) = portal
+                    .split_once("->")
+                    .unwrap_or_else(|| panic!("Expected portal like 1,5 -> 3,0, got: {}", line));
+                optics = optics.with_portal(parse_coords(from), parse_coords(to));
+                continue;
+            }
+
+            let (symbol, definition) = line
+                .split_once('=')
+                .unwrap_or_else(|| panic!("Expected tile like '#' = absorber, got: {}", line));
+            let symbol: Vec<char> = symbol.trim().trim_matches('\'').chars().collect();
+            if symbol.len() != 1 {
+                panic!("Expected a single character tile, got: {}", line);
+            }
+
+            let mut parts = definition.split(',').map(str::trim);
+            let mut behaviour = match parts.next().unwrap_or("") {
+                "empty" => TileBehaviour::empty(),
+                "absorber" => TileBehaviour::absorber(),
+                "forward_mirror" => TileBehaviour::forward_mirror(),
+                "back_mirror" => TileBehaviour::back_mirror(),
+                "horizontal_splitter" => TileBehaviour::horizontal_splitter(),
+                "vertical_splitter" => TileBehaviour::vertical_splitter(),
+                "four_way_splitter" => TileBehaviour::four_way_splitter(),
+                unknown => panic!("Expected a known tile behaviour, got: {}", unknown),
+            };
+            for output in parts {
+                let (entering, leaving) = output.split_once("->").unwrap_or_else(|| {
+                    panic!("Expected output like left -> up down, got: {}", output)
+                });
+                let leaving: Vec<Direction> =
+                    leaving.split_whitespace().map(parse_direction).collect();
+                behaviour = behaviour.with_output(parse_direction(entering.trim()), &leaving);
+            }
+
+            optics = optics.with_tile(symbol[0], behaviour);
+        }
+
+        optics
+    }
+}
+
+fn parse_coords(coords: &str) -> (usize, usize) {
+    let numbers: Vec<usize> = coords
+        .trim()
+        .split(',')
+        .map(|number| {
+            number
+                .trim()
+                .parse()
+                .unwrap_or_else(|_| panic!("Expected row,col coordinates, got: {}", coords))
+        })
+        .collect();
+    match numbers[..] {
+        [row, col] => (row, col),
+        _ => panic!("Expected row,col coordinates, got: {}", coords),
+    }
+}
+
+fn parse_direction(direction: &str) -> Direction {
+    match direction {
+        "down" => Down,
+        "up" => Up,
+        "right" => Right,
+        "left" => Left,
+        unknown => panic!("Expected up, down, left or right, got: {}", unknown),
+    }
+}
+
+/// Marks a tile that stopped a beam, alongside the bits for beams leaving it.
+const ABSORBED: u8 = 0b10000;
+const ENERGIZED_FILL: &str = "#ffd700";
+/// From no tiles energised up to as many as the best entry point:
+const HEAT_SHADES: [char; 10] = [' ', '.', ':', '-', '=', '+', '*', '#', '%', '@'];
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RenderFormat {
+    Ascii,
+    Svg,
+}
+
+struct Canvas {
+    rows: usize,
+    cols: usize,
+    cells: Vec<(char, Option<String>, Option<String>)>,
+}
 
-struct Contraption {
-    grid: Vec<Mirror>,
+impl Canvas {
+    fn new(rows: usize, cols: usize) -> Canvas {
+        Canvas {
+            rows,
+            cols,
+            cells: vec![(' ', None, None); rows * cols],
+        }
+    }
+
+    fn set(
+        &mut self,
+        row: usize,
+        col: usize,
+        symbol: char,
+        fill: Option<String>,
+        title: Option<String>,
+    ) {
+        self.cells[row * self.cols + col] = (symbol, fill, title);
+    }
+
+    /// SVG draws each cell as a unit square, leaving out blank and `.` symbols.
+    fn render(&self, format: RenderFormat) -> String {
+        match format {
+            RenderFormat::Ascii => self
+                .cells
+                .chunks(self.cols)
+                .map(|row| row.iter().map(|(symbol, _, _)| symbol).collect::<String>())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            RenderFormat::Svg => {
+                let mut result = format!(
+                    "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\" font-family=\"monospace\" font-size=\"0.8\" text-anchor=\"middle\">\n",
+                    self.cols, self.rows
+                );
+                for (index, (symbol, fill, title)) in self.cells.iter().enumerate() {
+                    let (row, col) = (index / self.cols, index % self.cols);
+                    if let Some(fill) = fill {
+                        let title = title
+                            .as_ref()
+                            .map(|title| format!("<title>{}</title>", title))
+                            .unwrap_or_default();
+                        result += &format!(
+                            "<rect x=\"{}\" y=\"{}\" width=\"1\" height=\"1\" fill=\"{}\">{}</rect>\n",
+                            col, row, fill, title
+                        );
+                    }
+                    if *symbol != ' ' && *symbol != '.' {
+                        let symbol = match symbol {
+                            '<' => "&lt;".to_owned(),
+                            '>' => "&gt;".to_owned(),
+                            '&' => "&amp;".to_owned(),
+                            symbol => symbol.to_string(),
+                        };
+                        result +=
+                            &format!("<text x=\"{}.5\" y=\"{}.8\">{}</text>\n", col, row, symbol);
+                    }
+                }
+                result += "</svg>\n";
+                result
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct TileSet {
+    words: Vec<u64>,
+}
+
+impl TileSet {
+    fn new(size: usize) -> TileSet {
+        TileSet {
+            words: vec![0; size.div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, index: usize) {
+        self.words[index / 64] |= 1 << (index % 64);
+    }
+
+    fn union_with(&mut self, other: &TileSet) {
+        for (word, other) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other;
+        }
+    }
+
+    fn contains(&self, index: usize) -> bool {
+        self.words[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+}
+
+/// A splitting tile by grid index, with a bit for each direction the beams leave in.
+type Split = (usize, u8);
+
+/// The tiles a beam crosses until it leaves the grid, is absorbed, or is split.
+struct Segment {
+    tiles: TileSet,
+    split_at: Option<Split>,
+}
+
+/// The tiles energised after each split, shared by splits whose beams reach each other.
+struct SegmentGraph {
+    component_of: HashMap<Split, usize>,
+    component_tiles: Vec<TileSet>,
+}
+
+pub struct Contraption {
+    /// Indices into `behaviours`.
+    grid: Vec<usize>,
+    symbols: Vec<char>,
+    behaviours: Vec<TileBehaviour>,
+    /// Where a beam entering each tile continues from.
+    portals: HashMap<usize, usize>,
     energized: Vec<u8>,
     rows: usize,
     cols: usize,
@@ -22,24 +376,48 @@ struct Pos {
     col: i64,
 }
 
+impl Pos {
+    fn reversed(&self) -> Pos {
+        Pos {
+            row: -self.row,
+            col: -self.col,
+        }
+    }
+}
+
+fn split_mask(outputs: &[Direction]) -> u8 {
+    outputs.iter().fold(0, |mask, output| {
+        mask | 1 << direction_index(output.offset())
+    })
+}
+
+fn direction_index(direction: Pos) -> usize {
+    match direction {
+        Pos { row: 1, col: 0 } => 0,
+        Pos { row: -1, col: 0 } => 1,
+        Pos { row: 0, col: 1 } => 2,
+        Pos { row: 0, col: -1 } => 3,
+        unexpected => unreachable!("Unexpected direction {:?}", unexpected),
+    }
+}
+
 impl Contraption {
-    fn new(input: &str) -> Contraption {
-        let mut grid: Vec<Mirror> = Vec::new();
-        let mut energized: Vec<u8> = Vec::new();
+    pub fn parse(input: &str, optics: &Optics) -> Contraption {
+        let mut grid: Vec<usize> = Vec::new();
         let mut rows = 0;
         let mut cols = 0;
 
         for (row, line) in input.split('\n').enumerate() {
             for (col, char) in line.chars().enumerate() {
-                grid.push(match char {
-                    '.' => Empty,
-                    '/' => Right,
-                    '\\' => Left,
-                    '-' => Horizontal,
-                    '|' => Vertical,
-                    unknown => unreachable!("Encountered unexpected character: {}", unknown),
-                });
-                energized.push(0);
+                grid.push(
+                    optics
+                        .tiles
+                        .iter()
+                        .position(|(symbol, _)| *symbol == char)
+                        .unwrap_or_else(|| {
+                            panic!("Expected the optics to have a tile for character: {}", char)
+                        }),
+                );
 
                 if col > cols {
                     cols = col;
@@ -55,27 +433,59 @@ impl Contraption {
         rows += 1;
         cols += 1;
 
+        let portals = optics
+            .portals
+            .iter()
+            .map(|&((from_row, from_col), (to_row, to_col))| {
+                assert!(
+                    from_row < rows && to_row < rows && from_col < cols && to_col < cols,
+                    "Portals should be inside the contraption."
+                );
+                (from_row * cols + from_col, to_row * cols + to_col)
+            })
+            .collect();
+
         Contraption {
+            energized: vec![0; grid.len()],
             grid,
-            energized,
+            symbols: optics.tiles.iter().map(|(symbol, _)| *symbol).collect(),
+            behaviours: optics
+                .tiles
+                .iter()
+                .map(|(_, behaviour)| behaviour.clone())
+                .collect(),
+            portals,
             rows,
             cols,
         }
     }
 
-    fn get(&self, pos: Pos) -> Mirror {
-        self.grid[pos.row as usize * self.rows + pos.col as usize]
+    fn new(input: &str) -> Contraption {
+        Contraption::parse(input, &Optics::standard())
+    }
+
+    fn index(&self, pos: Pos) -> usize {
+        pos.row as usize * self.cols + pos.col as usize
+    }
+
+    fn in_bounds(&self, pos: Pos) -> bool {
+        pos.row >= 0 && pos.col >= 0 && pos.row < self.rows as i64 && pos.col < self.cols as i64
+    }
+
+    fn pos(&self, index: usize) -> Pos {
+        Pos {
+            row: (index / self.cols) as i64,
+            col: (index % self.cols) as i64,
+        }
+    }
+
+    fn behaviour(&self, index: usize) -> &TileBehaviour {
+        &self.behaviours[self.grid[index]]
     }
 
     fn energize(&mut self, pos: Pos, direction: Pos) -> bool {
-        let index = pos.row as usize * self.rows + pos.col as usize;
-        let bits = match direction {
-            Pos { row: 1, col: 0 } => 0b1,
-            Pos { row: -1, col: 0 } => 0b10,
-            Pos { row: 0, col: 1 } => 0b100,
-            Pos { row: 0, col: -1 } => 0b1000,
-            unexpected => unreachable!("Unexpected direction energized {:?}", unexpected),
-        };
+        let index = self.index(pos);
+        let bits = 1 << direction_index(direction);
         let existing = self.energized[index];
         if existing & bits > 0 {
             return true;
@@ -114,97 +524,217 @@ impl Contraption {
                 continue;
             }
 
-            let mirror = self.get(pos);
-            if mirror == Empty
-                || (mirror == Horizontal && direction.col != 0)
-                || (mirror == Vertical && direction.row != 0)
-            {
-                queue.push((pos, direction));
-                continue;
+            let pos = match self.portals.get(&self.index(pos)) {
+                Some(&to) => {
+                    self.energize(pos, direction);
+                    self.pos(to)
+                }
+                None => pos,
+            };
+            let index = self.index(pos);
+            let outputs = self.behaviours[self.grid[index]].outputs(direction);
+            if outputs.is_empty() {
+                self.energized[index] |= ABSORBED;
             }
-
-            if mirror == Horizontal {
-                queue.push((pos, Pos { row: 0, col: -1 }));
-                queue.push((pos, Pos { row: 0, col: 1 }));
-            } else if mirror == Vertical {
-                queue.push((pos, Pos { row: -1, col: 0 }));
-                queue.push((pos, Pos { row: 1, col: 0 }));
-            } else if mirror == Right {
-                queue.push((
-                    pos,
-                    match direction {
-                        Pos { row: 1, col: 0 } => Pos { row: 0, col: -1 },
-                        Pos { row: -1, col: 0 } => Pos { row: 0, col: 1 },
-                        Pos { row: 0, col: 1 } => Pos { row: -1, col: 0 },
-                        Pos { row: 0, col: -1 } => Pos { row: 1, col: 0 },
-                        unknown => unreachable!(
-                            "Hit a right-angled mirror with an unacceptable direction {:?}",
-                            unknown
-                        ),
-                    },
-                ));
-            } else if mirror == Left {
-                queue.push((
-                    pos,
-                    match direction {
-                        Pos { row: 1, col: 0 } => Pos { row: 0, col: 1 },
-                        Pos { row: -1, col: 0 } => Pos { row: 0, col: -1 },
-                        Pos { row: 0, col: 1 } => Pos { row: 1, col: 0 },
-                        Pos { row: 0, col: -1 } => Pos { row: -1, col: 0 },
-                        unknown => unreachable!(
-                            "Hit a left-angled mirror with an unacceptable direction {:?}",
-                            unknown
-                        ),
-                    },
-                ));
-            } else {
-                unreachable!("Mirror has an impossible value {:?}", mirror);
+            for output in outputs {
+                queue.push((pos, output.offset()));
             }
         }
 
         self.energized_total()
     }
 
-    fn optimal_beam(&mut self) -> usize {
-        let mut max = 0;
-
-        for col in 0..self.cols {
-            max = max.max(self.simulate_beams(
+    /// The position just outside the grid a beam enters from, with its direction.
+    fn entry_point(&self, edge: Edge, index: usize) -> (Pos, Pos) {
+        let (rows, cols, index) = (self.rows as i64, self.cols as i64, index as i64);
+        match edge {
+            Edge::Top => (
                 Pos {
                     row: -1,
-                    col: col as i64,
+                    col: index,
                 },
                 Pos { row: 1, col: 0 },
-            ));
-
-            max = max.max(self.simulate_beams(
+            ),
+            Edge::Bottom => (
                 Pos {
-                    row: self.rows as i64,
-                    col: col as i64,
+                    row: rows,
+                    col: index,
                 },
                 Pos { row: -1, col: 0 },
-            ));
-        }
-
-        for row in 0..self.rows {
-            max = max.max(self.simulate_beams(
+            ),
+            Edge::Left => (
                 Pos {
-                    row: row as i64,
+                    row: index,
                     col: -1,
                 },
                 Pos { row: 0, col: 1 },
-            ));
-
-            max = max.max(self.simulate_beams(
+            ),
+            Edge::Right => (
                 Pos {
-                    row: row as i64,
-                    col: self.cols as i64,
+                    row: index,
+                    col: cols,
                 },
                 Pos { row: 0, col: -1 },
-            ));
+            ),
+        }
+    }
+
+    fn entry_points(&self) -> Vec<(Edge, usize, Pos, Pos)> {
+        let top_and_bottom = [Edge::Top, Edge::Bottom]
+            .into_iter()
+            .flat_map(|edge| (0..self.cols).map(move |index| (edge, index)));
+        let left_and_right = [Edge::Left, Edge::Right]
+            .into_iter()
+            .flat_map(|edge| (0..self.rows).map(move |index| (edge, index)));
+
+        top_and_bottom
+            .chain(left_and_right)
+            .map(|(edge, index)| {
+                let (pos, direction) = self.entry_point(edge, index);
+                (edge, index, pos, direction)
+            })
+            .collect()
+    }
+
+    /// Follows a beam from just before `pos` until it leaves the grid or splits.
+    fn trace(&self, mut pos: Pos, mut direction: Pos) -> Segment {
+        let mut tiles = TileSet::new(self.grid.len());
+        let mut seen = TileSet::new(self.grid.len() * 4);
+
+        loop {
+            pos = Pos {
+                row: pos.row + direction.row,
+                col: pos.col + direction.col,
+            };
+            if !self.in_bounds(pos) {
+                return Segment {
+                    tiles,
+                    split_at: None,
+                };
+            }
+
+            let mut index = self.index(pos);
+            tiles.insert(index);
+            if let Some(&to) = self.portals.get(&index) {
+                index = to;
+                pos = self.pos(to);
+                tiles.insert(index);
+            }
+
+            let state = index * 4 + direction_index(direction);
+            if seen.contains(state) {
+                // Mirrors have sent the beam round in a loop:
+                return Segment {
+                    tiles,
+                    split_at: None,
+                };
+            }
+            seen.insert(state);
+
+            match self.behaviour(index).outputs(direction) {
+                [output] => direction = output.offset(),
+                [] => {
+                    return Segment {
+                        tiles,
+                        split_at: None,
+                    }
+                }
+                outputs => {
+                    return Segment {
+                        tiles,
+                        split_at: Some((index, split_mask(outputs))),
+                    }
+                }
+            }
+        }
+    }
+
+    fn segment_graph(&self) -> SegmentGraph {
+        // Every way a tile can split a beam, with the tiles and splits its outgoing beams
+        // reach:
+        let mut nodes: Vec<(Split, TileSet, Vec<Split>)> = Vec::new();
+        let mut node_of: HashMap<Split, usize> = HashMap::new();
+        for index in 0..self.grid.len() {
+            for direction in DIRECTIONS {
+                let outputs = self.behaviour(index).outputs(direction.offset());
+                let split = (index, split_mask(outputs));
+                if outputs.len() < 2 || node_of.contains_key(&split) {
+                    continue;
+                }
+
+                let mut tiles = TileSet::new(self.grid.len());
+                tiles.insert(index);
+                let mut successors = Vec::new();
+                for output in outputs {
+                    let segment = self.trace(self.pos(index), output.offset());
+                    tiles.union_with(&segment.tiles);
+                    successors.extend(segment.split_at);
+                }
+
+                node_of.insert(split, nodes.len());
+                nodes.push((split, tiles, successors));
+            }
+        }
+        let successors: Vec<Vec<usize>> = nodes
+            .iter()
+            .map(|(_, _, successors)| successors.iter().map(|split| node_of[split]).collect())
+            .collect();
+
+        // Components come after every component they reach:
+        let components = graph::strongly_connected_components(&successors);
+
+        let mut component_of_node = vec![0; nodes.len()];
+        for (component, members) in components.iter().enumerate() {
+            for &member in members {
+                component_of_node[member] = component;
+            }
         }
 
-        max
+        let mut component_tiles: Vec<TileSet> = Vec::with_capacity(components.len());
+        for (component, members) in components.iter().enumerate() {
+            let mut tiles = TileSet::new(self.grid.len());
+            for &member in members {
+                tiles.union_with(&nodes[member].1);
+                for &successor in successors[member].iter() {
+                    let successor = component_of_node[successor];
+                    if successor != component {
+                        tiles.union_with(&component_tiles[successor]);
+                    }
+                }
+            }
+            component_tiles.push(tiles);
+        }
+
+        let component_of = nodes
+            .iter()
+            .enumerate()
+            .map(|(node, (split, _, _))| (*split, component_of_node[node]))
+            .collect();
+
+        SegmentGraph {
+            component_of,
+            component_tiles,
+        }
+    }
+
+    pub fn all_entry_points(&self) -> Vec<EntryResult> {
+        let graph = self.segment_graph();
+
+        self.entry_points()
+            .into_iter()
+            .map(|(edge, index, pos, direction)| {
+                let mut segment = self.trace(pos, direction);
+                if let Some(split_at) = segment.split_at {
+                    let component = graph.component_of[&split_at];
+                    segment.tiles.union_with(&graph.component_tiles[component]);
+                }
+                EntryResult {
+                    edge,
+                    index,
+                    energized: segment.tiles.len(),
+                }
+            })
+            .collect()
     }
 
     fn energized_total(&self) -> usize {
@@ -213,54 +743,161 @@ impl Contraption {
             .fold(0, |accum, item| if *item > 0 { accum + 1 } else { accum })
     }
 
-    #[allow(dead_code)]
-    fn print_grid(&self) {
-        for (index, mirror) in self.grid.iter().enumerate() {
-            if index > 0 && index % self.cols == 0 {
-                println!();
-            }
+    /// Arrows for beams crossing empty tiles, or a count where several cross.
+    fn beams_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.rows, self.cols);
+        for (index, &bits) in self.energized.iter().enumerate() {
+            let symbol = match (self.behaviour(index).is_empty(), bits.count_ones()) {
+                (true, 0) | (false, _) => self.symbols[self.grid[index]],
+                (true, 1) => ['v', '^', '>', '<'][bits.trailing_zeros() as usize],
+                (true, count) => char::from_digit(count, 10).expect("Count should be a digit."),
+            };
+            let fill = (bits != 0).then(|| ENERGIZED_FILL.to_owned());
+            canvas.set(index / self.cols, index % self.cols, symbol, fill, None);
+        }
+        canvas
+    }
 
-            print!(
-                "{}",
-                match mirror {
-                    Empty => '.',
-                    Left => '\\',
-                    Right => '/',
-                    Horizontal => '-',
-                    Vertical => '|',
-                }
-            );
+    fn energized_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.rows, self.cols);
+        for (index, &bits) in self.energized.iter().enumerate() {
+            let (symbol, fill) = if bits != 0 {
+                ('#', Some(ENERGIZED_FILL.to_owned()))
+            } else {
+                ('.', None)
+            };
+            canvas.set(index / self.cols, index % self.cols, symbol, fill, None);
         }
-        println!("\n");
+        canvas
     }
 
-    #[allow(dead_code)]
-    fn print_energized(&self) {
-        for (index, is_energized) in self.energized.iter().enumerate() {
-            if index > 0 && index % self.cols == 0 {
-                println!();
-            }
+    /// Each entry point around the edge, shaded relative to the best one.
+    fn heat_map_canvas(&self) -> Canvas {
+        let mut canvas = Canvas::new(self.rows + 2, self.cols + 2);
+        for (index, &tile) in self.grid.iter().enumerate() {
+            canvas.set(
+                index / self.cols + 1,
+                index % self.cols + 1,
+                self.symbols[tile],
+                None,
+                None,
+            );
+        }
 
-            print!("{}", if *is_energized > 0 { '#' } else { '.' });
+        let results = self.all_entry_points();
+        let max = results
+            .iter()
+            .map(|result| result.energized)
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        for result in results {
+            let (row, col) = match result.edge {
+                Edge::Top => (0, result.index + 1),
+                Edge::Bottom => (self.rows + 1, result.index + 1),
+                Edge::Left => (result.index + 1, 0),
+                Edge::Right => (result.index + 1, self.cols + 1),
+            };
+            let shade = HEAT_SHADES[result.energized * (HEAT_SHADES.len() - 1) / max];
+            let cool = 255 - result.energized * 255 / max;
+            canvas.set(
+                row,
+                col,
+                shade,
+                Some(format!("#ff{:02x}{:02x}", cool, cool)),
+                Some(result.energized.to_string()),
+            );
         }
-        println!("\n");
+        canvas
     }
+
+    pub fn energized(&mut self, edge: Edge, index: usize) -> usize {
+        let (pos, direction) = self.entry_point(edge, index);
+        self.simulate_beams(pos, direction)
+    }
+
+    pub fn render_beams(&mut self, edge: Edge, index: usize, format: RenderFormat) -> String {
+        self.energized(edge, index);
+        self.beams_canvas().render(format)
+    }
+
+    pub fn render_energized(&mut self, edge: Edge, index: usize, format: RenderFormat) -> String {
+        self.energized(edge, index);
+        self.energized_canvas().render(format)
+    }
+
+    pub fn render_heat_map(&self, format: RenderFormat) -> String {
+        self.heat_map_canvas().render(format)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// `index` is a column for the top and bottom edges and a row for the left and right.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct EntryResult {
+    pub edge: Edge,
+    pub index: usize,
+    pub energized: usize,
+}
+
+pub fn day16_entry_points(input: &str) -> Vec<EntryResult> {
+    Contraption::new(input).all_entry_points()
+}
+
+pub fn day16_render_beams(input: &str, edge: Edge, index: usize, format: RenderFormat) -> String {
+    Contraption::new(input).render_beams(edge, index, format)
+}
+
+pub fn day16_render_energized(
+    input: &str,
+    edge: Edge,
+    index: usize,
+    format: RenderFormat,
+) -> String {
+    Contraption::new(input).render_energized(edge, index, format)
+}
+
+pub fn day16_render_heat_map(input: &str, format: RenderFormat) -> String {
+    Contraption::new(input).render_heat_map(format)
 }
 
 pub fn day16_part_1(input: &str) -> usize {
-    let mut contraption = Contraption::new(input);
-    contraption.simulate_beams(Pos { row: 0, col: -1 }, Pos { row: 0, col: 1 })
+    Contraption::new(input).energized(Edge::Left, 0)
 }
 
 pub fn day16_part_2(input: &str) -> usize {
-    let mut contraption = Contraption::new(input);
-    contraption.simulate_beams(Pos { row: 0, col: -1 }, Pos { row: 0, col: 1 });
-    contraption.optimal_beam()
+    day16_entry_points(input)
+        .iter()
+        .map(|entry| entry.energized)
+        .max()
+        .expect("Expected at least one entry point.")
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day16::{day16_part_1, day16_part_2};
+    use crate::day16::{
+        day16_entry_points, day16_part_1, day16_part_2, day16_render_beams, day16_render_energized,
+        day16_render_heat_map, Contraption, Direction, Edge, EntryResult, Optics, RenderFormat,
+        TileBehaviour,
+    };
+
+    const EXAMPLE: &str = r".|...\....
+|.-.\.....
+.....|-...
+........|.
+..........
+.........\
+..../.\\..
+.-.-/..|..
+.|....-|.\
+..//.|....";
 
     #[test]
     pub fn part1_example() {
@@ -299,4 +936,171 @@ mod tests {
             51
         );
     }
+
+    #[test]
+    pub fn entry_points() {
+        let results = day16_entry_points(EXAMPLE);
+        assert_eq!(results.len(), 40);
+        assert!(results.contains(&EntryResult {
+            edge: Edge::Top,
+            index: 3,
+            energized: 51
+        }));
+        assert!(results.contains(&EntryResult {
+            edge: Edge::Left,
+            index: 0,
+            energized: 46
+        }));
+
+        // The segment graph agrees with simulating every beam directly:
+        let mut contraption = Contraption::new(EXAMPLE);
+        for ((_, _, pos, direction), result) in contraption.entry_points().into_iter().zip(results)
+        {
+            assert_eq!(contraption.simulate_beams(pos, direction), result.energized);
+        }
+    }
+
+    #[test]
+    pub fn rectangular_loops() {
+        // A grid that isn't square, where mirrors send beams round in loops:
+        let input = r"./-\.
+.\-/.
+./..\
+.\../";
+        let mut contraption = Contraption::new(input);
+        for ((_, _, pos, direction), result) in contraption
+            .entry_points()
+            .into_iter()
+            .zip(contraption.all_entry_points())
+        {
+            assert_eq!(contraption.simulate_beams(pos, direction), result.energized);
+        }
+    }
+
+    #[test]
+    pub fn render() {
+        assert_eq!(
+            day16_render_beams(EXAMPLE, Edge::Left, 0, RenderFormat::Ascii),
+            r">|<<<\....
+|v-.\^....
+.v...|->>>
+.v...v^.|.
+.v...v^...
+.v...v^..\
+.v../2\\..
+<->-/vv|..
+.|<<<2-|.\
+.v//.|.v.."
+        );
+        assert_eq!(
+            day16_render_energized(EXAMPLE, Edge::Left, 0, RenderFormat::Ascii),
+            "######....
+.#...#....
+.#...#####
+.#...##...
+.#...##...
+.#...##...
+.#..####..
+########..
+.#######..
+.#...#.#.."
+        );
+
+        let heat_map = day16_render_heat_map(EXAMPLE, RenderFormat::Ascii);
+        let lines: Vec<&str> = heat_map.split('\n').collect();
+        assert_eq!(lines.len(), 12);
+        assert_eq!(&lines[0][4..5], "@");
+        // The beam from the left of the top row energises 46 tiles out of the best 51:
+        assert_eq!(&lines[1][..11], r"%.|...\....");
+
+        let svg = day16_render_heat_map(EXAMPLE, RenderFormat::Svg);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 12 12""#));
+        assert_eq!(svg.matches("<rect").count(), 40);
+        assert!(svg.contains(
+            r##"<rect x="4" y="0" width="1" height="1" fill="#ff0000"><title>51</title></rect>"##
+        ));
+
+        let svg = day16_render_beams(EXAMPLE, Edge::Left, 0, RenderFormat::Svg);
+        assert_eq!(svg.matches("<rect").count(), 46);
+        assert!(svg.contains(r#"<text x="0.5" y="0.8">&gt;</text>"#));
+    }
+
+    #[test]
+    pub fn custom_optics() {
+        let optics = Optics::standard()
+            .with_tile('#', TileBehaviour::absorber())
+            .with_tile('*', TileBehaviour::four_way_splitter())
+            .with_tile(
+                '>',
+                TileBehaviour::forward_mirror().with_output(Direction::Left, &[Direction::Left]),
+            );
+
+        let mut contraption = Contraption::parse("..#..", &optics);
+        assert_eq!(contraption.energized(Edge::Left, 0), 3);
+
+        let mut contraption = Contraption::parse(".*.\n...\n...", &optics);
+        assert_eq!(contraption.energized(Edge::Top, 1), 5);
+
+        // The one-way mirror turns beams heading right, but not those heading left:
+        let mut contraption = Contraption::parse(".....\n..>..", &optics);
+        assert_eq!(contraption.energized(Edge::Left, 1), 4);
+        assert_eq!(contraption.energized(Edge::Right, 1), 5);
+        assert_eq!(
+            contraption.render_beams(Edge::Left, 1, RenderFormat::Ascii),
+            "..^..\n>>>.."
+        );
+
+        let mut contraption =
+            Contraption::parse(".....\n.....", &optics.clone().with_portal((0, 2), (1, 0)));
+        assert_eq!(contraption.energized(Edge::Left, 0), 8);
+        // Heading down from the portal's exit leaves the grid straight away:
+        assert_eq!(contraption.energized(Edge::Top, 2), 2);
+
+        // The segment graph agrees with simulating every beam directly:
+        let optics = optics
+            .with_portal((1, 5), (3, 0))
+            .with_portal((4, 4), (0, 3));
+        let mut contraption = Contraption::parse(
+            r"..*.\.|.
+.>.#.-..
+/..*...\
+..|.>.#.
+\.-./*..",
+            &optics,
+        );
+        let results = contraption.all_entry_points();
+        for ((_, _, pos, direction), result) in contraption.entry_points().into_iter().zip(results)
+        {
+            assert_eq!(contraption.simulate_beams(pos, direction), result.energized);
+        }
+    }
+
+    #[test]
+    pub fn optics_config() {
+        let optics = Optics::parse(
+            "'#' = absorber
+            '*' = four_way_splitter
+
+            '>' = forward_mirror, left -> left
+            portal 0,2 -> 1,0",
+        );
+        assert_eq!(
+            Contraption::parse(".....\n..>..", &optics).energized(Edge::Right, 1),
+            5
+        );
+        assert_eq!(
+            Contraption::parse(".....\n.....", &optics).energized(Edge::Left, 0),
+            8
+        );
+        assert_eq!(
+            Contraption::parse(".#...\n.....", &optics).energized(Edge::Left, 0),
+            2
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected the optics to have a tile for character: #")]
+    pub fn unknown_tile() {
+        Contraption::parse("..#..", &Optics::standard());
+    }
 }
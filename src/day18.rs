@@ -1,9 +1,9 @@
-use std::{collections::HashSet, str::Split};
+use std::collections::HashSet;
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
 struct Pos {
-    row: i64,
-    col: i64,
+    row: i128,
+    col: i128,
 }
 
 impl Pos {
@@ -15,6 +15,7 @@ impl Pos {
     }
 }
 
+#[allow(dead_code)]
 trait GridOps {
     fn bounds(&self) -> (Pos, Pos);
     fn print(&self);
@@ -23,12 +24,12 @@ trait GridOps {
 impl GridOps for HashSet<Pos> {
     fn bounds(&self) -> (Pos, Pos) {
         let mut min = Pos {
-            row: i64::MAX,
-            col: i64::MAX,
+            row: i128::MAX,
+            col: i128::MAX,
         };
         let mut max = Pos {
-            row: i64::MIN,
-            col: i64::MIN,
+            row: i128::MIN,
+            col: i128::MIN,
         };
         for pos in self.iter() {
             if pos.row < min.row {
@@ -64,48 +65,38 @@ impl GridOps for HashSet<Pos> {
     }
 }
 
-struct Part1Iter<'a>(Split<'a, char>);
-impl<'a> Iterator for Part1Iter<'a> {
-    type Item = (char, u8);
+/// Which part of each dig plan line gives the direction and distance.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Encoding {
+    /// `R 6`
+    Direction,
+    /// `(#70c710)`, with the distance in the first five hex digits and the direction in
+    /// the last.
+    Colour,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(line) = self.0.next() {
-            let mut split = line.split(' ');
+fn parse_step(line: &str, encoding: Encoding) -> (char, i128) {
+    let mut split = line.split(' ');
+    match encoding {
+        Encoding::Direction => {
             let direction = split
                 .next()
                 .expect("Expected direction.")
                 .chars()
                 .next()
                 .expect("Expect single char for direction.");
-            let distance: u8 = split
+            let distance: i128 = split
                 .next()
                 .expect("Expected distance.")
                 .parse()
                 .expect("Expected distance to be a valid number.");
 
-            Some((direction, distance))
-        } else {
-            None
+            (direction, distance)
         }
-    }
-}
-
-impl<'a> Part1Iter<'a> {
-    fn new(input: &'a str) -> Part1Iter<'a> {
-        Part1Iter(input.split('\n'))
-    }
-}
-
-struct Part2Iter<'a>(Split<'a, char>);
-impl<'a> Iterator for Part2Iter<'a> {
-    type Item = (char, i64);
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if let Some(line) = self.0.next() {
-            let hex = line.split(' ').nth(2).expect("Expected to find hex codes.");
-
+        Encoding::Colour => {
+            let hex = split.nth(2).expect("Expected to find hex codes.");
             let distance =
-                i64::from_str_radix(&hex[2..7], 16).expect("Expected a valid hex number.");
+                i128::from_str_radix(&hex[2..7], 16).expect("Expected a valid hex number.");
             let direction = match &hex[7..8] {
                 "0" => 'R',
                 "1" => 'D',
@@ -114,83 +105,221 @@ impl<'a> Iterator for Part2Iter<'a> {
                 unknown => unreachable!("Unexpected direction hex number: {}", unknown),
             };
 
-            Some((direction, distance))
-        } else {
-            None
+            (direction, distance)
         }
     }
 }
 
-impl<'a> Part2Iter<'a> {
-    fn new(input: &'a str) -> Part2Iter<'a> {
-        Part2Iter(input.split('\n'))
-    }
+/// The `#rrggbb` colour painted on the edge of the trench.
+fn parse_colour(line: &str) -> &str {
+    line.split(' ')
+        .nth(2)
+        .expect("Expected to find hex codes.")
+        .trim_start_matches('(')
+        .trim_end_matches(')')
 }
 
-fn shoelace(points: &[Pos]) -> i64 {
-    let sum: i64 = points
-        .iter()
-        .zip(points.iter().skip(1))
-        .map(|(p1, p2)| p1.col * p2.row - p1.row * p2.col)
-        .sum();
-    sum / 2
+#[derive(PartialEq, Eq, Debug)]
+pub enum DigPlanError {
+    /// The trench doesn't finish where it started.
+    NotClosed,
+    /// The trench crosses or runs along itself where steps `first` and `second` meet.
+    SelfIntersecting { first: usize, second: usize },
 }
 
-pub fn day18_part_1(input: &str) -> i64 {
-    let mut pos = Pos { row: 0, col: 0 };
-    let mut points: Vec<Pos> = Vec::new();
-    let mut total_distance: i64 = 0;
-    points.push(pos);
-
-    for (direction, distance) in Part1Iter::new(input) {
-        let direction = match direction {
-            'U' => Pos { row: -1, col: 0 },
-            'D' => Pos { row: 1, col: 0 },
-            'L' => Pos { row: 0, col: -1 },
-            'R' => Pos { row: 0, col: 1 },
-            unknown => unreachable!("Unexpected direction: {}", unknown),
+/// The trench dug by a dig plan, as the corners of a rectilinear polygon.
+#[derive(Debug)]
+pub struct DigPlan {
+    /// Starts and ends at the origin. Steps with a distance of zero are skipped.
+    corners: Vec<Pos>,
+    /// The colour of the edge from each corner to the next.
+    colours: Vec<String>,
+    boundary: i128,
+}
+
+impl DigPlan {
+    pub fn parse(input: &str, encoding: Encoding) -> Result<DigPlan, DigPlanError> {
+        let mut pos = Pos { row: 0, col: 0 };
+        let mut corners: Vec<Pos> = vec![pos];
+        let mut colours: Vec<String> = Vec::new();
+        let mut boundary: i128 = 0;
+
+        for line in input.split('\n') {
+            let (direction, distance) = parse_step(line, encoding);
+            if distance == 0 {
+                continue;
+            }
+            let direction = match direction {
+                'U' => Pos { row: -1, col: 0 },
+                'D' => Pos { row: 1, col: 0 },
+                'L' => Pos { row: 0, col: -1 },
+                'R' => Pos { row: 0, col: 1 },
+                unknown => unreachable!("Unexpected direction: {}", unknown),
+            };
+
+            pos = pos.add(&Pos {
+                row: direction.row * distance,
+                col: direction.col * distance,
+            });
+            boundary += distance;
+            corners.push(pos);
+            colours.push(parse_colour(line).to_owned());
+        }
+
+        if pos != corners[0] {
+            return Err(DigPlanError::NotClosed);
+        }
+
+        let plan = DigPlan {
+            corners,
+            colours,
+            boundary,
         };
+        plan.check_intersections()?;
+        Ok(plan)
+    }
+
+    fn check_intersections(&self) -> Result<(), DigPlanError> {
+        let segments: Vec<(Pos, Pos)> = self
+            .corners
+            .windows(2)
+            .map(|pair| (pair[0].min(pair[1]), pair[0].max(pair[1])))
+            .collect();
+
+        for (first, (a_min, a_max)) in segments.iter().enumerate() {
+            for (second, (b_min, b_max)) in segments.iter().enumerate().skip(first + 1) {
+                let overlap_min = Pos {
+                    row: a_min.row.max(b_min.row),
+                    col: a_min.col.max(b_min.col),
+                };
+                let overlap_max = Pos {
+                    row: a_max.row.min(b_max.row),
+                    col: a_max.col.min(b_max.col),
+                };
+                if overlap_min.row > overlap_max.row || overlap_min.col > overlap_max.col {
+                    continue;
+                }
 
-        pos = pos.add(&Pos {
-            row: direction.row * distance as i64,
-            col: direction.col * distance as i64,
-        });
-        total_distance += distance as i64;
-        points.push(pos);
+                // Consecutive steps always share a corner, but nothing more:
+                let adjacent = second == first + 1 || (first == 0 && second == segments.len() - 1);
+                if !adjacent || overlap_min != overlap_max {
+                    return Err(DigPlanError::SelfIntersecting { first, second });
+                }
+            }
+        }
+
+        Ok(())
     }
 
-    shoelace(&points) + (total_distance / 2) + 1
-}
+    /// The number of cubes dug out along the trench.
+    pub fn boundary_length(&self) -> i128 {
+        self.boundary
+    }
 
-pub fn day18_part_2(input: &str) -> i64 {
-    let mut pos = Pos { row: 0, col: 0 };
-    let mut points: Vec<Pos> = Vec::new();
-    let mut total_distance: i64 = 0;
-    points.push(pos);
-
-    for (direction, distance) in Part2Iter::new(input) {
-        let direction = match direction {
-            'U' => Pos { row: -1, col: 0 },
-            'D' => Pos { row: 1, col: 0 },
-            'L' => Pos { row: 0, col: -1 },
-            'R' => Pos { row: 0, col: 1 },
-            unknown => unreachable!("Unexpected direction: {}", unknown),
+    /// The number of cubes enclosed by the trench, not counting the trench itself.
+    pub fn interior_area(&self) -> i128 {
+        let double_area: i128 = self
+            .corners
+            .windows(2)
+            .map(|pair| pair[0].col * pair[1].row - pair[0].row * pair[1].col)
+            .sum();
+
+        // The corners lie on the centres of trench cubes, so by Pick's theorem the cubes
+        // strictly inside are the polygon's area less half the trench, plus one:
+        double_area.abs() / 2 - self.boundary / 2 + 1
+    }
+
+    /// The number of cubes dug out once the interior is excavated too.
+    pub fn volume(&self) -> i128 {
+        self.interior_area() + self.boundary
+    }
+
+    /// Draws the lagoon as an SVG, with the interior filled and each edge of the trench
+    /// in its colour. Every cube is two units wide so cube centres land on whole numbers.
+    ///
+    /// With `compress`, each distinct row and column is drawn one cube apart, so plans
+    /// with huge distances still produce a readable image.
+    pub fn to_svg(&self, compress: bool) -> String {
+        let mut rows: Vec<i128> = self.corners.iter().map(|pos| pos.row).collect();
+        let mut cols: Vec<i128> = self.corners.iter().map(|pos| pos.col).collect();
+        rows.sort_unstable();
+        rows.dedup();
+        cols.sort_unstable();
+        cols.dedup();
+
+        let scale = |values: &[i128], value: i128| -> i128 {
+            let value = if compress {
+                values
+                    .binary_search(&value)
+                    .expect("Expected a corner value.") as i128
+            } else {
+                value - values[0]
+            };
+            value * 2 + 1
         };
+        let points: Vec<(i128, i128)> = self
+            .corners
+            .iter()
+            .map(|pos| (scale(&cols, pos.col), scale(&rows, pos.row)))
+            .collect();
+        let width = scale(&cols, cols[cols.len() - 1]) + 1;
+        let height = scale(&rows, rows[rows.len() - 1]) + 1;
+
+        let mut result = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {} {}\">\n",
+            width, height
+        );
+        let polygon: Vec<String> = points.iter().map(|(x, y)| format!("{},{}", x, y)).collect();
+        result += &format!(
+            "<polygon points=\"{}\" fill=\"#808080\"/>\n",
+            polygon.join(" ")
+        );
+        for (pair, colour) in points.windows(2).zip(self.colours.iter()) {
+            result += &format!(
+                "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"2\" stroke-linecap=\"square\"/>\n",
+                pair[0].0, pair[0].1, pair[1].0, pair[1].1, colour
+            );
+        }
+        result += "</svg>\n";
 
-        pos = pos.add(&Pos {
-            row: direction.row * distance,
-            col: direction.col * distance,
-        });
-        total_distance += distance;
-        points.push(pos);
+        result
     }
+}
+
+pub fn day18_part_1(input: &str) -> i64 {
+    DigPlan::parse(input, Encoding::Direction)
+        .expect("Expected a valid dig plan.")
+        .volume()
+        .try_into()
+        .expect("Result should fit in an i64.")
+}
 
-    shoelace(&points) + (total_distance / 2) + 1
+pub fn day18_part_2(input: &str) -> i64 {
+    DigPlan::parse(input, Encoding::Colour)
+        .expect("Expected a valid dig plan.")
+        .volume()
+        .try_into()
+        .expect("Result should fit in an i64.")
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day18::{day18_part_1, day18_part_2};
+    use crate::day18::{day18_part_1, day18_part_2, DigPlan, DigPlanError, Encoding};
+
+    const EXAMPLE: &str = "R 6 (#70c710)
+D 5 (#0dc571)
+L 2 (#5713f0)
+D 2 (#d2c081)
+R 2 (#59c680)
+D 2 (#411b91)
+L 5 (#8ceee2)
+U 2 (#caa173)
+L 1 (#1b58a2)
+U 2 (#caa171)
+R 2 (#7807d2)
+U 3 (#a77fa3)
+L 2 (#015232)
+U 2 (#7a21e3)";
 
     #[test]
     pub fn part1_example() {
@@ -237,4 +366,92 @@ U 2 (#7a21e3)"
             952408144115
         );
     }
+
+    #[test]
+    pub fn dig_plan_measurements() {
+        let plan = DigPlan::parse(EXAMPLE, Encoding::Direction).expect("Plan should be valid.");
+        assert_eq!(plan.boundary_length(), 38);
+        assert_eq!(plan.interior_area(), 24);
+        assert_eq!(plan.volume(), 62);
+
+        // Distances far beyond the puzzle input still fit:
+        let plan = DigPlan::parse(
+            "R 9000000000000 (#000000)
+D 9000000000000 (#000000)
+L 9000000000000 (#000000)
+U 9000000000000 (#000000)",
+            Encoding::Direction,
+        )
+        .expect("Plan should be valid.");
+        assert_eq!(plan.volume(), 9000000000001i128 * 9000000000001);
+    }
+
+    #[test]
+    pub fn dig_plan_validation() {
+        assert_eq!(
+            DigPlan::parse("R 2 (#000000)\nD 2 (#000000)", Encoding::Direction).unwrap_err(),
+            DigPlanError::NotClosed
+        );
+
+        // A figure of eight crossing itself in the middle:
+        assert_eq!(
+            DigPlan::parse(
+                "R 2 (#000000)
+D 4 (#000000)
+R 2 (#000000)
+U 2 (#000000)
+L 4 (#000000)
+U 2 (#000000)",
+                Encoding::Direction
+            )
+            .unwrap_err(),
+            DigPlanError::SelfIntersecting {
+                first: 1,
+                second: 4
+            }
+        );
+
+        // Doubling back along the previous step:
+        assert_eq!(
+            DigPlan::parse(
+                "R 2 (#000000)
+L 1 (#000000)
+D 1 (#000000)
+L 1 (#000000)
+U 1 (#000000)",
+                Encoding::Direction
+            )
+            .unwrap_err(),
+            DigPlanError::SelfIntersecting {
+                first: 0,
+                second: 1
+            }
+        );
+    }
+
+    #[test]
+    pub fn dig_plan_svg() {
+        let input = "R 2 (#ff0000)
+D 1 (#00ff00)
+L 2 (#0000ff)
+U 1 (#ffffff)";
+        let plan = DigPlan::parse(input, Encoding::Direction).expect("Plan should be valid.");
+        assert_eq!(
+            plan.to_svg(false),
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 6 4">
+<polygon points="1,1 5,1 5,3 1,3 1,1" fill="#808080"/>
+<line x1="1" y1="1" x2="5" y2="1" stroke="#ff0000" stroke-width="2" stroke-linecap="square"/>
+<line x1="5" y1="1" x2="5" y2="3" stroke="#00ff00" stroke-width="2" stroke-linecap="square"/>
+<line x1="5" y1="3" x2="1" y2="3" stroke="#0000ff" stroke-width="2" stroke-linecap="square"/>
+<line x1="1" y1="3" x2="1" y2="1" stroke="#ffffff" stroke-width="2" stroke-linecap="square"/>
+</svg>
+"##
+        );
+
+        let plan = DigPlan::parse(EXAMPLE, Encoding::Colour).expect("Plan should be valid.");
+        let svg = plan.to_svg(true);
+        assert!(svg.starts_with(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 14 12">"#));
+        assert!(svg.contains(r##"stroke="#70c710""##));
+        assert_eq!(svg.matches("<line").count(), 14);
+    }
 }
@@ -1,263 +1,715 @@
+use crate::graph;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 #[derive(Clone, Copy)]
-#[repr(usize)]
-enum Category {
-    ExtremelyCool = 0usize,
-    Musical = 1usize,
-    Aerodynamic = 2usize,
-    Shiny = 3usize,
-}
-use Category::*;
-
 enum Op {
     LessThan,
+    LessThanOrEqual,
     GreaterThan,
+    GreaterThanOrEqual,
+    Equal,
 }
 use Op::*;
 
 struct Rule<'a> {
-    category: Category,
+    /// Index into `Workflows::categories`.
+    category: usize,
     op: Op,
     rhs: i64,
     destination: &'a str,
 }
 
+impl<'a> Rule<'a> {
+    /// The ratings of the category this rule accepts.
+    fn interval(&self) -> Range {
+        match self.op {
+            LessThan => Range::new(i64::MIN, self.rhs.saturating_sub(1)),
+            LessThanOrEqual => Range::new(i64::MIN, self.rhs),
+            GreaterThan => Range::new(self.rhs.saturating_add(1), i64::MAX),
+            GreaterThanOrEqual => Range::new(self.rhs, i64::MAX),
+            Equal => Range::new(self.rhs, self.rhs),
+        }
+    }
+
+    /// Whether the part's rating matches, or `None` if the part has no rating to check.
+    fn matches(&self, part: &Part) -> Option<bool> {
+        let rating = part.ratings[self.category]?;
+        Some(self.interval().contains(rating))
+    }
+
+    fn describe(&self, categories: &[&str]) -> String {
+        let op = match self.op {
+            LessThan => "<",
+            LessThanOrEqual => "<=",
+            GreaterThan => ">",
+            GreaterThanOrEqual => ">=",
+            Equal => "==",
+        };
+        format!("{}{}{}", categories[self.category], op, self.rhs)
+    }
+
+    /// Splits `ranges` into the ratings this rule matches, if any, and the regions it
+    /// passes on to the next rule.
+    fn split(&self, ranges: &[Range]) -> (Option<Vec<Range>>, Vec<Vec<Range>>) {
+        let range = ranges[self.category];
+        let interval = self.interval();
+        let with_range = |range: Range| -> Option<Vec<Range>> {
+            if range.is_empty() {
+                return None;
+            }
+            let mut result = ranges.to_vec();
+            result[self.category] = range;
+            Some(result)
+        };
+
+        let matched = with_range(Range::new(
+            range.start.max(interval.start),
+            range.end.min(interval.end),
+        ));
+        let below = with_range(Range::new(
+            range.start,
+            range.end.min(interval.start.saturating_sub(1)),
+        ));
+        let above = with_range(Range::new(
+            range.start.max(interval.end.saturating_add(1)),
+            range.end,
+        ));
+
+        (matched, below.into_iter().chain(above).collect())
+    }
+}
+
 struct RuleList<'a> {
     rules: Vec<Rule<'a>>,
     final_destination: &'a str,
 }
 
+impl<'a> RuleList<'a> {
+    fn destinations(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.rules
+            .iter()
+            .map(|rule| rule.destination)
+            .chain(std::iter::once(self.final_destination))
+    }
+}
+
 struct Part {
-    categories: [i64; 4],
+    /// Indexed like `Workflows::categories`, missing where the part has no rating.
+    ratings: Vec<Option<i64>>,
+    total: i64,
 }
 
-fn day19_parse<'a>(input: &'a str) -> (HashMap<&str, RuleList<'a>>, Vec<Part>) {
-    let mut halves = input.split("\n\n");
-    let rules_text = halves.next().expect("Expected to find list of rules.");
-    let parts_text = halves.next().expect("Expected to find list of parts.");
-    let rule_regexp =
-        Regex::new(r"^([xmas]+)([<>])(\d+):([a-zA-Z]+)$").expect("Regexp should be valid.");
-
-    let mut rule_lists: HashMap<&str, RuleList<'a>> = HashMap::new();
-
-    for line in rules_text.split('\n') {
-        let mut name_and_remainder = line.split('{');
-        let name = name_and_remainder.next().expect("Expected to find name.");
-        let remainder = name_and_remainder
-            .next()
-            .expect("Expected to find rule list.");
-        let mut list = RuleList {
-            rules: Vec::new(),
-            final_destination: "",
+/// An inclusive range of ratings.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Range {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Range {
+    pub fn new(start: i64, end: i64) -> Range {
+        Range { start, end }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start > self.end
+    }
+
+    fn contains(&self, value: i64) -> bool {
+        self.start <= value && value <= self.end
+    }
+
+    fn len(&self) -> i64 {
+        if self.is_empty() {
+            0
+        } else {
+            1 + self.end - self.start
+        }
+    }
+}
+
+/// The workflows from the first half of the input, along with every rating category
+/// named by the rules or the parts and the bounds on each category's ratings.
+pub struct Workflows<'a> {
+    categories: Vec<&'a str>,
+    bounds: Vec<Range>,
+    rule_lists: HashMap<&'a str, RuleList<'a>>,
+}
+
+impl<'a> Workflows<'a> {
+    /// Parses the workflows, discovering categories from the parts if the input has them
+    /// and then from the rules. Every category starts with ratings from 1 to 4000.
+    pub fn parse(input: &'a str) -> Workflows<'a> {
+        let mut halves = input.split("\n\n");
+        let rules_text = halves.next().expect("Expected to find list of rules.");
+        let rule_regexp = Regex::new(r"^([a-zA-Z]+)(<=|>=|==|<|>)(-?\d+):([a-zA-Z]+)$")
+            .expect("Regexp should be valid.");
+
+        let mut workflows = Workflows {
+            categories: Vec::new(),
+            bounds: Vec::new(),
+            rule_lists: HashMap::new(),
         };
 
-        let rule_strs: Vec<_> = remainder.split(',').collect();
-        for rule_str in rule_strs.iter().take(rule_strs.len() - 1) {
-            let (_, [category_str, op_str, rhs_str, destination]) = rule_regexp
-                .captures(rule_str)
-                .expect("Regexp should match.")
-                .extract();
-
-            let category = match category_str {
-                "x" => ExtremelyCool,
-                "m" => Musical,
-                "a" => Aerodynamic,
-                "s" => Shiny,
-                unknown => unreachable!("Encounted unexpected category: {}", unknown),
-            };
+        // Categories take the order the parts list them in, then any only named by rules:
+        for line in halves.next().unwrap_or("").split('\n') {
+            for rating in line.trim_matches(|c| c == '{' || c == '}').split(',') {
+                if let Some((category, _)) = rating.split_once('=') {
+                    workflows.category_index(category);
+                }
+            }
+        }
 
-            let op = match op_str {
-                "<" => LessThan,
-                ">" => GreaterThan,
-                unknown => unreachable!("Encountered unexpected op: {}", unknown),
+        for line in rules_text.split('\n') {
+            let mut name_and_remainder = line.split('{');
+            let name = name_and_remainder.next().expect("Expected to find name.");
+            let remainder = name_and_remainder
+                .next()
+                .expect("Expected to find rule list.");
+            let mut list = RuleList {
+                rules: Vec::new(),
+                final_destination: "",
             };
 
-            let rhs: i64 = rhs_str
-                .parse()
-                .expect("Expected rule to have a i64 right-hand side.");
+            let rule_strs: Vec<_> = remainder.split(',').collect();
+            for rule_str in rule_strs.iter().take(rule_strs.len() - 1) {
+                let (_, [category_str, op_str, rhs_str, destination]) = rule_regexp
+                    .captures(rule_str)
+                    .expect("Regexp should match.")
+                    .extract();
+
+                let op = match op_str {
+                    "<" => LessThan,
+                    "<=" => LessThanOrEqual,
+                    ">" => GreaterThan,
+                    ">=" => GreaterThanOrEqual,
+                    "==" => Equal,
+                    unknown => unreachable!("Encountered unexpected op: {}", unknown),
+                };
+
+                let rhs: i64 = rhs_str
+                    .parse()
+                    .expect("Expected rule to have a i64 right-hand side.");
+
+                list.rules.push(Rule {
+                    category: workflows.category_index(category_str),
+                    op,
+                    rhs,
+                    destination,
+                });
+            }
+            list.final_destination = rule_strs[rule_strs.len() - 1].trim_end_matches('}');
 
-            list.rules.push(Rule {
-                category,
-                op,
-                rhs,
-                destination,
-            });
+            workflows.rule_lists.insert(name, list);
+        }
+
+        workflows
+    }
+
+    /// Finds or adds the category called `name`.
+    fn category_index(&mut self, name: &'a str) -> usize {
+        if let Some(index) = self.categories.iter().position(|&other| other == name) {
+            return index;
+        }
+        self.categories.push(name);
+        self.bounds.push(Range::new(1, 4000));
+        self.categories.len() - 1
+    }
+
+    /// Limits ratings in `category` to `bounds`, adding the category if no rule or part
+    /// mentions it.
+    pub fn with_bounds(mut self, category: &'a str, bounds: Range) -> Workflows<'a> {
+        let index = self.category_index(category);
+        self.bounds[index] = bounds;
+        self
+    }
+
+    pub fn categories(&self) -> &[&'a str] {
+        &self.categories
+    }
+
+    /// Parses a part like `{x=787,m=2655,a=1222,s=2876}`. Every rating must be in a
+    /// known category and within its bounds, as those are all `check` follows.
+    fn parse_part(&self, line: &'a str) -> Result<Part, Diagnostic<'a>> {
+        let mut part = Part {
+            ratings: vec![None; self.categories.len()],
+            total: 0,
+        };
+
+        for rating in line.trim_matches(|c| c == '{' || c == '}').split(',') {
+            let (category, value) = rating
+                .split_once('=')
+                .expect("Expected rating to be category=value.");
+            let value: i64 = value.parse().expect("Parts numbers should be valid.");
+            let index = self
+                .categories
+                .iter()
+                .position(|&other| other == category)
+                .ok_or(Diagnostic::UnknownCategory { category })?;
+            if !self.bounds[index].contains(value) {
+                return Err(Diagnostic::RatingOutOfBounds {
+                    category,
+                    rating: value,
+                });
+            }
+            part.ratings[index] = Some(value);
+            part.total += value;
         }
-        list.final_destination = rule_strs[rule_strs.len() - 1].trim_end_matches('}');
 
-        rule_lists.insert(name, list);
+        Ok(part)
     }
 
-    let mut parts: Vec<Part> = Vec::new();
+    /// Runs the part through the workflows, describing each rule it is checked against
+    /// in `trace`. Returns whether the part is accepted.
+    fn run_part(&self, part: &Part, trace: &mut Vec<String>) -> Result<bool, Diagnostic<'a>> {
+        let mut name = "in";
+
+        while !is_terminal(name) {
+            let rule_list = self
+                .rule_lists
+                .get(name)
+                .expect("Checked workflows should have every destination.");
+            let mut destination: Option<&str> = None;
+
+            for rule in rule_list.rules.iter() {
+                let description = rule.describe(&self.categories);
+                let matches = rule.matches(part).ok_or(Diagnostic::MissingRating {
+                    workflow: name,
+                    category: self.categories[rule.category],
+                })?;
+                if matches {
+                    trace.push(format!("{}: {} -> {}", name, description, rule.destination));
+                    destination = Some(rule.destination);
+                    break;
+                }
+                trace.push(format!("{}: {} not matched", name, description));
+            }
 
-    let parts_regexp = Regex::new(r"^\{x=(\d+),m=(\d+),a=(\d+),s=(\d+)\}$")
-        .expect("Expect parts regexp to be valid.");
-    for line in parts_text.split('\n') {
-        let [x, m, a, s] = parts_regexp
-            .captures(line)
-            .expect("Expect parts regexp to match.")
-            .extract()
-            .1
-            .map(|num_str| {
-                num_str
-                    .parse::<i64>()
-                    .expect("Parts numbers should be valid.")
+            name = destination.unwrap_or_else(|| {
+                trace.push(format!(
+                    "{}: otherwise -> {}",
+                    name, rule_list.final_destination
+                ));
+                rule_list.final_destination
             });
+        }
 
-        parts.push(Part {
-            categories: [x, m, a, s],
-        });
+        Ok(name == "A")
     }
 
-    (rule_lists, parts)
-}
+    /// Follows every possible part from `in`, reporting the `Cycle` and
+    /// `MissingWorkflow` diagnostics that some part would actually run into. Parts that
+    /// can't reach a mistake are unaffected by it.
+    pub fn check(&self) -> Result<(), Vec<Diagnostic<'a>>> {
+        let mut errors: Vec<Diagnostic> = Vec::new();
+        let mut queue: Vec<(Vec<Range>, Vec<&str>)> = Vec::new();
+        if self.rule_lists.contains_key("in") {
+            queue.push((self.bounds.clone(), vec!["in"]));
+        } else {
+            errors.push(missing_in());
+        }
 
-pub fn day19_part_1(input: &str) -> i64 {
-    let (rule_lists, parts) = day19_parse(input);
+        while let Some((ranges, path)) = queue.pop() {
+            let name = path[path.len() - 1];
+            for (ranges, destination, _) in self.branches(&self.rule_lists[name], ranges) {
+                if is_terminal(destination) {
+                    continue;
+                }
 
-    parts
-        .into_iter()
-        .filter(|p| {
-            let mut rule_list_current = rule_lists
-                .get("in")
-                .expect("Initial rule should always be found.");
-
-            'outer: loop {
-                for rule in rule_list_current.rules.iter() {
-                    if match rule.op {
-                        GreaterThan => p.categories[rule.category as usize] > rule.rhs,
-                        LessThan => p.categories[rule.category as usize] < rule.rhs,
-                    } {
-                        if rule.destination == "A" {
-                            return true;
-                        } else if rule.destination == "R" {
-                            return false;
-                        } else {
-                            rule_list_current = rule_lists
-                                .get(rule.destination)
-                                .expect("Destination rule should always be found.");
-                            continue 'outer;
-                        }
-                    }
+                if let Some(start) = path.iter().position(|&other| other == destination) {
+                    let mut workflows = path[start..].to_vec();
+                    workflows.sort();
+                    errors.push(Diagnostic::Cycle { workflows });
+                } else if !self.rule_lists.contains_key(destination) {
+                    errors.push(Diagnostic::MissingWorkflow {
+                        workflow: name,
+                        destination,
+                    });
+                } else {
+                    let mut path = path.clone();
+                    path.push(destination);
+                    queue.push((ranges, path));
                 }
+            }
+        }
+
+        errors.sort();
+        errors.dedup();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
 
-                if rule_list_current.final_destination == "A" {
-                    return true;
-                } else if rule_list_current.final_destination == "R" {
-                    return false;
+    pub fn accepts(&self, part: &'a str) -> Result<bool, Vec<Diagnostic<'a>>> {
+        self.check()?;
+        let part = self.parse_part(part).map_err(|error| vec![error])?;
+        self.run_part(&part, &mut Vec::new())
+            .map_err(|error| vec![error])
+    }
+
+    /// The rules `part` is checked against on its way through the workflows, one per
+    /// line, ending with the `A` or `R` it reaches.
+    pub fn explain_part(&self, part: &'a str) -> Result<Vec<String>, Vec<Diagnostic<'a>>> {
+        self.check()?;
+        let part = self.parse_part(part).map_err(|error| vec![error])?;
+        let mut trace = Vec::new();
+        self.run_part(&part, &mut trace)
+            .map_err(|error| vec![error])?;
+        Ok(trace)
+    }
+
+    /// Splits `ranges` between the rules of `rule_list`, returning each non-empty region
+    /// with its destination and the index of the rule that sends it there. The final
+    /// destination has the index after the last rule.
+    fn branches(
+        &self,
+        rule_list: &RuleList<'a>,
+        ranges: Vec<Range>,
+    ) -> Vec<(Vec<Range>, &'a str, usize)> {
+        let mut result = Vec::new();
+        let mut remaining = vec![ranges];
+
+        for (index, rule) in rule_list.rules.iter().enumerate() {
+            let mut not_matched = Vec::new();
+            for ranges in remaining {
+                let (matched, rest) = rule.split(&ranges);
+                if let Some(matched) = matched {
+                    result.push((matched, rule.destination, index));
+                }
+                not_matched.extend(rest);
+            }
+            remaining = not_matched;
+        }
+
+        for ranges in remaining {
+            result.push((ranges, rule_list.final_destination, rule_list.rules.len()));
+        }
+
+        result
+    }
+
+    /// Splits every possible part into the regions accepted by the workflows. The
+    /// regions don't overlap.
+    pub fn accepted_regions(&self) -> Result<Vec<AcceptedRegion<'a>>, Vec<Diagnostic<'a>>> {
+        self.check()?;
+        let mut accepted: Vec<AcceptedRegion> = Vec::new();
+        let mut queue: Vec<(Vec<Range>, Vec<&str>)> = vec![(self.bounds.clone(), vec!["in"])];
+
+        while let Some((ranges, path)) = queue.pop() {
+            let rule_list = self
+                .rule_lists
+                .get(path[path.len() - 1])
+                .expect("Checked workflows should have every destination.");
+
+            for (ranges, destination, _) in self.branches(rule_list, ranges) {
+                if destination == "R" {
+                    continue;
+                }
+
+                let mut path = path.clone();
+                path.push(destination);
+                if destination == "A" {
+                    accepted.push(AcceptedRegion {
+                        ranges: self.categories.iter().cloned().zip(ranges).collect(),
+                        path,
+                    });
                 } else {
-                    rule_list_current = rule_lists
-                        .get(rule_list_current.final_destination)
-                        .expect("Final destination should always be found.");
+                    queue.push((ranges, path));
                 }
             }
+        }
+
+        Ok(accepted)
+    }
+
+    pub fn analyse(&self) -> Vec<Diagnostic<'a>> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+        analyse_unreachable(&self.rule_lists, &mut diagnostics);
+        analyse_cycles(&self.rule_lists, &mut diagnostics);
+        analyse_rules(self, &mut diagnostics);
+
+        let mut cache: HashMap<&str, Option<&str>> = HashMap::new();
+        for &name in self.rule_lists.keys() {
+            if let Some(destination) =
+                always_same(&self.rule_lists, name, &mut HashSet::new(), &mut cache)
+            {
+                diagnostics.push(Diagnostic::AlwaysSame {
+                    workflow: name,
+                    destination,
+                });
+            }
+        }
+
+        diagnostics.sort();
+        diagnostics
+    }
+}
+
+/// Every part with ratings inside `ranges` is accepted by following the workflows in
+/// `path`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct AcceptedRegion<'a> {
+    /// Each category with the ratings accepted in it.
+    pub ranges: Vec<(&'a str, Range)>,
+    /// Workflow names from `in`, ending with `A`.
+    pub path: Vec<&'a str>,
+}
+
+impl<'a> AcceptedRegion<'a> {
+    pub fn combinations(&self) -> i64 {
+        self.ranges.iter().map(|(_, range)| range.len()).product()
+    }
+
+    /// The path as text, like `in -> px -> qkq -> A`.
+    pub fn describe_path(&self) -> String {
+        self.path.join(" -> ")
+    }
+}
+
+pub fn day19_part_1(input: &str) -> i64 {
+    let workflows = Workflows::parse(input);
+    workflows
+        .check()
+        .expect("Expected workflows without cycles or missing workflows.");
+    let parts_text = input
+        .split("\n\n")
+        .nth(1)
+        .expect("Expected to find list of parts.");
+
+    parts_text
+        .split('\n')
+        .map(|line| {
+            workflows
+                .parse_part(line)
+                .expect("Expected part with known categories and ratings within bounds.")
+        })
+        .filter(|p| {
+            workflows
+                .run_part(p, &mut Vec::new())
+                .expect("Expected part to have every rating its workflows check.")
         })
-        .map(|p| p.categories.into_iter().sum::<i64>())
+        .map(|p| p.total)
         .sum()
 }
 
-#[derive(Debug, Clone, Copy)]
-struct Range {
-    start: i64,
-    end: i64,
+pub fn day19_explain_part<'a>(
+    input: &'a str,
+    part: &'a str,
+) -> Result<Vec<String>, Vec<Diagnostic<'a>>> {
+    Workflows::parse(input).explain_part(part)
 }
 
-impl Range {
-    fn new() -> Range {
-        Range {
-            start: 1,
-            end: 4000,
-        }
-    }
+pub fn day19_accepted_regions(input: &str) -> Result<Vec<AcceptedRegion<'_>>, Vec<Diagnostic<'_>>> {
+    Workflows::parse(input).accepted_regions()
 }
 
 pub fn day19_part_2(input: &str) -> i64 {
-    let rules_lists = day19_parse(input).0;
-    let start = rules_lists
-        .get("in")
-        .expect("Starting rule should be found.");
-    let range_initial: [Range; 4] = [Range::new(), Range::new(), Range::new(), Range::new()];
-    let mut approved_ranges: Vec<[Range; 4]> = Vec::new();
-    let mut queue: Vec<([Range; 4], &RuleList)> = vec![(range_initial, start)];
-
-    while let Some((range, rule_list)) = queue.pop() {
-        let mut range_not_matched = range;
-
-        for rule in rule_list.rules.iter() {
-            let matched = match rule.op {
-                LessThan => {
-                    let mut range_matched = range_not_matched;
-
-                    if range_matched[rule.category as usize].end >= rule.rhs {
-                        range_matched[rule.category as usize].end = rule.rhs - 1;
-                    }
-
-                    if range_not_matched[rule.category as usize].start < rule.rhs {
-                        range_not_matched[rule.category as usize].start = rule.rhs;
-                    }
-
-                    range_matched
-                }
-                GreaterThan => {
-                    let mut range_matched = range_not_matched;
+    day19_accepted_regions(input)
+        .expect("Expected workflows without cycles or missing workflows.")
+        .iter()
+        .map(AcceptedRegion::combinations)
+        .sum()
+}
 
-                    if range_matched[rule.category as usize].start <= rule.rhs {
-                        range_matched[rule.category as usize].start = rule.rhs + 1;
-                    }
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Diagnostic<'a> {
+    /// No path from `in` leads to this workflow.
+    Unreachable { workflow: &'a str },
+    /// These workflows can send a part around in a loop.
+    Cycle { workflows: Vec<&'a str> },
+    /// The rule at index `rule` can't match any part that earlier rules let through. An
+    /// index equal to the number of rules means the final destination.
+    DeadRule { workflow: &'a str, rule: usize },
+    /// A rule sends parts to a workflow that doesn't exist. A missing `in` workflow is
+    /// reported as `in` sending parts to itself.
+    MissingWorkflow {
+        workflow: &'a str,
+        destination: &'a str,
+    },
+    /// Every branch ends at the same `A` or `R`, so the workflow could be replaced by it.
+    AlwaysSame {
+        workflow: &'a str,
+        destination: &'a str,
+    },
+    /// A part rates a category that no rule or part in the input names.
+    UnknownCategory { category: &'a str },
+    /// A part's rating lies outside the bounds of its category.
+    RatingOutOfBounds { category: &'a str, rating: i64 },
+    /// A rule in `workflow` checks a category the part has no rating for.
+    MissingRating {
+        workflow: &'a str,
+        category: &'a str,
+    },
+}
 
-                    if range_not_matched[rule.category as usize].end > rule.rhs {
-                        range_not_matched[rule.category as usize].end = rule.rhs;
-                    }
+fn is_terminal(destination: &str) -> bool {
+    destination == "A" || destination == "R"
+}
 
-                    range_matched
-                }
-            };
+/// Every part starts at `in`, so there is nothing to run without it.
+fn missing_in<'a>() -> Diagnostic<'a> {
+    Diagnostic::MissingWorkflow {
+        workflow: "in",
+        destination: "in",
+    }
+}
 
-            if rule.destination == "A" {
-                approved_ranges.push(matched);
-            } else if rule.destination != "R" {
-                queue.push((
-                    matched,
-                    rules_lists
-                        .get(rule.destination)
-                        .expect("Expected to find destination."),
-                ));
+fn analyse_unreachable<'a>(
+    rule_lists: &HashMap<&'a str, RuleList<'a>>,
+    diagnostics: &mut Vec<Diagnostic<'a>>,
+) {
+    if !rule_lists.contains_key("in") {
+        diagnostics.push(missing_in());
+    }
+
+    let mut reached: HashSet<&str> = HashSet::new();
+    let mut queue: Vec<&str> = vec!["in"];
+    while let Some(name) = queue.pop() {
+        if !reached.insert(name) {
+            continue;
+        }
+        if let Some(rule_list) = rule_lists.get(name) {
+            queue.extend(rule_list.destinations());
+        }
+    }
+
+    for &name in rule_lists.keys() {
+        if !reached.contains(name) {
+            diagnostics.push(Diagnostic::Unreachable { workflow: name });
+        }
+    }
+}
+
+/// Reports every strongly connected component of workflows that loops.
+fn analyse_cycles<'a>(
+    rule_lists: &HashMap<&'a str, RuleList<'a>>,
+    diagnostics: &mut Vec<Diagnostic<'a>>,
+) {
+    let names: Vec<&str> = rule_lists.keys().cloned().collect();
+    let node_of: HashMap<&str, usize> = names
+        .iter()
+        .enumerate()
+        .map(|(node, &name)| (name, node))
+        .collect();
+    let successors: Vec<Vec<usize>> = names
+        .iter()
+        .map(|&name| {
+            rule_lists[name]
+                .destinations()
+                .filter_map(|destination| node_of.get(destination).copied())
+                .collect()
+        })
+        .collect();
+
+    for component in graph::strongly_connected_components(&successors) {
+        let mut component: Vec<&str> = component.into_iter().map(|node| names[node]).collect();
+        let loops_to_itself = rule_lists[component[0]]
+            .destinations()
+            .any(|destination| destination == component[0]);
+        if component.len() > 1 || loops_to_itself {
+            component.sort();
+            diagnostics.push(Diagnostic::Cycle {
+                workflows: component,
+            });
+        }
+    }
+}
+
+fn analyse_rules<'a>(workflows: &Workflows<'a>, diagnostics: &mut Vec<Diagnostic<'a>>) {
+    let rule_lists = &workflows.rule_lists;
+    for (&name, rule_list) in rule_lists.iter() {
+        let mut live = vec![false; rule_list.rules.len() + 1];
+        for (_, _, index) in workflows.branches(rule_list, workflows.bounds.clone()) {
+            live[index] = true;
+        }
+        for (index, live) in live.into_iter().enumerate() {
+            if !live {
+                diagnostics.push(Diagnostic::DeadRule {
+                    workflow: name,
+                    rule: index,
+                });
             }
         }
 
-        if rule_list.final_destination == "A" {
-            approved_ranges.push(range_not_matched);
-        } else if rule_list.final_destination != "R" {
-            queue.push((
-                range_not_matched,
-                rules_lists
-                    .get(rule_list.final_destination)
-                    .expect("Expected to find final destination."),
-            ));
+        for destination in rule_list.destinations() {
+            if !is_terminal(destination) && !rule_lists.contains_key(destination) {
+                diagnostics.push(Diagnostic::MissingWorkflow {
+                    workflow: name,
+                    destination,
+                });
+            }
         }
     }
+}
+
+/// The `A` or `R` every branch of `name` eventually ends at, if they all agree.
+fn always_same<'a>(
+    rule_lists: &HashMap<&'a str, RuleList<'a>>,
+    name: &'a str,
+    visiting: &mut HashSet<&'a str>,
+    cache: &mut HashMap<&'a str, Option<&'a str>>,
+) -> Option<&'a str> {
+    if is_terminal(name) {
+        return Some(name);
+    }
+    if let Some(result) = cache.get(name) {
+        return *result;
+    }
+    if !visiting.insert(name) {
+        return None;
+    }
 
-    let mut possibilities: i64 = 0;
-    for ranges in approved_ranges {
-        let mut range_combinations = 0;
-        for (i, range) in ranges.into_iter().enumerate() {
-            if i == 0 {
-                range_combinations = 1 + range.end - range.start;
-            } else {
-                range_combinations *= 1 + range.end - range.start;
+    let mut result: Option<&str> = None;
+    for destination in rule_lists.get(name)?.destinations() {
+        match always_same(rule_lists, destination, visiting, cache) {
+            Some(terminal) if result.is_none() || result == Some(terminal) => {
+                result = Some(terminal);
+            }
+            _ => {
+                result = None;
+                break;
             }
         }
-        possibilities += range_combinations;
     }
 
-    possibilities
+    visiting.remove(name);
+    cache.insert(name, result);
+    result
+}
+
+/// Checks the workflows for mistakes and simplifications, without running any parts.
+pub fn day19_analyse(input: &str) -> Vec<Diagnostic<'_>> {
+    Workflows::parse(input).analyse()
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::day19::{day19_part_1, day19_part_2};
+    use crate::day19::{
+        day19_accepted_regions, day19_analyse, day19_explain_part, day19_part_1, day19_part_2,
+        missing_in, Diagnostic, Range, Workflows,
+    };
+
+    const EXAMPLE: &str = "px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}
+{x=1679,m=44,a=2067,s=496}
+{x=2036,m=264,a=79,s=2244}
+{x=2461,m=1339,a=466,s=291}
+{x=2127,m=1623,a=2188,s=1013}";
 
     #[test]
     pub fn part1_example() {
@@ -310,4 +762,260 @@ hdj{m>838:A,pv}
             167409079868000
         );
     }
+
+    #[test]
+    pub fn analyse() {
+        assert_eq!(
+            day19_analyse(
+                "px{a<2006:qkq,m>2090:A,rfg}
+pv{a>1716:R,A}
+lnx{m>1548:A,A}
+rfg{s<537:gd,x>2440:R,A}
+qs{s>3448:A,lnx}
+qkq{x<1416:A,crn}
+crn{x>2662:A,R}
+in{s<1351:px,qqz}
+qqz{s>2770:qs,m<1801:hdj,R}
+gd{a>3333:R,R}
+hdj{m>838:A,pv}
+
+{x=787,m=2655,a=1222,s=2876}"
+            ),
+            vec![
+                Diagnostic::AlwaysSame {
+                    workflow: "gd",
+                    destination: "R"
+                },
+                Diagnostic::AlwaysSame {
+                    workflow: "lnx",
+                    destination: "A"
+                },
+                Diagnostic::AlwaysSame {
+                    workflow: "qs",
+                    destination: "A"
+                },
+            ]
+        );
+
+        assert_eq!(
+            day19_analyse(
+                "in{x<100:a,x>50:b,c}
+a{m>10:b,A}
+b{m<5:a,x>4000:R,s<1:A,A}
+c{x<50:A,x<10:R,nope}
+d{A}
+
+{x=1,m=2,a=3,s=4}"
+            ),
+            vec![
+                Diagnostic::Unreachable { workflow: "d" },
+                Diagnostic::Cycle {
+                    workflows: vec!["a", "b"]
+                },
+                Diagnostic::DeadRule {
+                    workflow: "b",
+                    rule: 1
+                },
+                Diagnostic::DeadRule {
+                    workflow: "b",
+                    rule: 2
+                },
+                Diagnostic::DeadRule {
+                    workflow: "c",
+                    rule: 1
+                },
+                Diagnostic::DeadRule {
+                    workflow: "in",
+                    rule: 2
+                },
+                Diagnostic::MissingWorkflow {
+                    workflow: "c",
+                    destination: "nope"
+                },
+                Diagnostic::AlwaysSame {
+                    workflow: "d",
+                    destination: "A"
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn accepted_regions() {
+        let regions = day19_accepted_regions(EXAMPLE).expect("Example should be valid.");
+        assert_eq!(
+            regions
+                .iter()
+                .map(|region| region.combinations())
+                .sum::<i64>(),
+            167409079868000
+        );
+
+        let region = regions
+            .iter()
+            .find(|region| region.describe_path() == "in -> px -> qkq -> A")
+            .expect("Expected qkq to accept parts.");
+        assert_eq!(
+            region.ranges,
+            vec![
+                ("x", Range::new(1, 1415)),
+                ("m", Range::new(1, 4000)),
+                ("a", Range::new(1, 2005)),
+                ("s", Range::new(1, 1350)),
+            ]
+        );
+        assert!(regions.iter().all(|region| region.path[0] == "in"));
+    }
+
+    #[test]
+    pub fn explain_part() {
+        assert_eq!(
+            day19_explain_part(EXAMPLE, "{x=787,m=2655,a=1222,s=2876}")
+                .expect("Example should be valid."),
+            vec![
+                "in: s<1351 not matched",
+                "in: otherwise -> qqz",
+                "qqz: s>2770 -> qs",
+                "qs: s>3448 not matched",
+                "qs: otherwise -> lnx",
+                "lnx: m>1548 -> A",
+            ]
+        );
+        assert_eq!(
+            day19_explain_part(EXAMPLE, "{x=1679,m=44,a=2067,s=496}")
+                .expect("Example should be valid."),
+            vec![
+                "in: s<1351 -> px",
+                "px: a<2006 not matched",
+                "px: m>2090 not matched",
+                "px: otherwise -> rfg",
+                "rfg: s<537 -> gd",
+                "gd: a>3333 not matched",
+                "gd: otherwise -> R",
+            ]
+        );
+    }
+
+    #[test]
+    pub fn custom_categories() {
+        let input = "in{speed>=100:fast,colour==3:A,weight<=10:A,R}
+fast{weight>50:R,A}
+
+{colour=3,speed=20}
+{speed=150,weight=60}
+{speed=100,weight=50,colour=1}";
+        let workflows = Workflows::parse(input);
+        assert_eq!(workflows.categories(), ["colour", "speed", "weight"]);
+        assert_eq!(workflows.accepts("{colour=3,speed=20}"), Ok(true));
+        assert_eq!(workflows.accepts("{speed=150,weight=60}"), Ok(false));
+        assert_eq!(
+            workflows.accepts("{speed=100,weight=50,colour=1}"),
+            Ok(true)
+        );
+        assert_eq!(
+            workflows.explain_part("{speed=99,colour=2,weight=10}"),
+            Ok(vec![
+                "in: speed>=100 not matched".to_owned(),
+                "in: colour==3 not matched".to_owned(),
+                "in: weight<=10 -> A".to_owned(),
+            ])
+        );
+        assert_eq!(day19_part_1(input), 23 + 151);
+
+        let workflows = Workflows::parse(input)
+            .with_bounds("colour", Range::new(0, 4))
+            .with_bounds("speed", Range::new(0, 199))
+            .with_bounds("weight", Range::new(1, 100));
+        let combinations: i64 = workflows
+            .accepted_regions()
+            .expect("Workflows should be valid.")
+            .iter()
+            .map(|region| region.combinations())
+            .sum();
+        // Fast parts need weight <= 50, slow ones colour 3 or weight <= 10:
+        assert_eq!(combinations, 5 * 100 * 50 + 100 * (100 + 4 * 10));
+    }
+
+    #[test]
+    pub fn bounds_in_analysis() {
+        let input = "in{x<=1:R,x>=10:R,x==5:A,A}";
+        assert!(Workflows::parse(input).analyse().is_empty());
+        assert_eq!(
+            Workflows::parse(input)
+                .with_bounds("x", Range::new(5, 5))
+                .analyse(),
+            vec![
+                Diagnostic::DeadRule {
+                    workflow: "in",
+                    rule: 0
+                },
+                Diagnostic::DeadRule {
+                    workflow: "in",
+                    rule: 1
+                },
+                Diagnostic::DeadRule {
+                    workflow: "in",
+                    rule: 3
+                },
+            ]
+        );
+    }
+
+    #[test]
+    pub fn faulty_workflows() {
+        let looping = Workflows::parse("in{x<5:a,R}\na{x>0:in,A}");
+        assert_eq!(
+            looping.accepts("{x=1}"),
+            Err(vec![Diagnostic::Cycle {
+                workflows: vec!["a", "in"]
+            }])
+        );
+        assert!(looping.accepted_regions().is_err());
+
+        let missing = Workflows::parse("in{x<5:nope,A}");
+        assert_eq!(
+            missing.explain_part("{x=1}"),
+            Err(vec![Diagnostic::MissingWorkflow {
+                workflow: "in",
+                destination: "nope"
+            }])
+        );
+        assert!(missing.accepted_regions().is_err());
+
+        let workflows = Workflows::parse("in{m<5:A,R}\n\n{x=1,m=2}");
+        assert_eq!(workflows.accepts("{m=1,x=9}"), Ok(true));
+        assert_eq!(
+            workflows.accepts("{x=1}"),
+            Err(vec![Diagnostic::MissingRating {
+                workflow: "in",
+                category: "m"
+            }])
+        );
+        assert_eq!(
+            workflows.explain_part("{x=1,m=2,s=3}"),
+            Err(vec![Diagnostic::UnknownCategory { category: "s" }])
+        );
+        assert_eq!(
+            workflows.accepts("{x=1,m=4001}"),
+            Err(vec![Diagnostic::RatingOutOfBounds {
+                category: "m",
+                rating: 4001
+            }])
+        );
+
+        let no_in = Workflows::parse("a{A}");
+        assert_eq!(no_in.accepts("{x=1}"), Err(vec![missing_in()]));
+        assert!(day19_analyse("a{A}").contains(&missing_in()));
+
+        // Mistakes no part can reach don't stop the workflows from running:
+        assert_eq!(day19_part_1("in{x<5:A,R}\nzz{x<5:nope,R}\n\n{x=1}"), 1);
+        assert_eq!(day19_part_2("in{x<5:a,R}\na{x>10:in,A}\n\n{x=1}"), 4);
+        // Though the lints still point out the loop in the workflow names:
+        assert_eq!(
+            day19_analyse("in{x<5:a,R}\na{x>10:in,A}"),
+            vec![Diagnostic::Cycle {
+                workflows: vec!["a", "in"]
+            }]
+        );
+    }
 }
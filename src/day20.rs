@@ -116,7 +116,7 @@ impl<'a> Module<'a> {
 
 type ModuleMap<'a> = HashMap<&'a str, Module<'a>>;
 
-fn day20_parse(input: &str) -> (ModuleMap, Module) {
+fn day20_parse(input: &str) -> (ModuleMap<'_>, Module<'_>) {
     let mut modules: ModuleMap = HashMap::new();
     let mut conjunctions: Vec<&str> = Vec::new();
 
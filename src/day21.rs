@@ -1,14 +1,13 @@
+use std::collections::HashMap;
+
 #[derive(Clone, Copy)]
 enum Tile {
     GardenPlot,
     Rock,
 }
-use std::collections::HashSet;
 
 use Tile::*;
 
-use crate::fnv1::BuildFnv1Hasher;
-
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 struct Pos {
     row: i64,
@@ -26,7 +25,7 @@ impl Pos {
 
 struct Garden {
     grid: Vec<Tile>,
-    occupied: HashSet<Pos>,
+    start: Pos,
     rows: i64,
     cols: i64,
     wrap: bool,
@@ -69,12 +68,9 @@ impl Garden {
         rows += 1;
         cols += 1;
 
-        let mut occupied = HashSet::new();
-        occupied.insert(start);
-
         Garden {
             grid,
-            occupied,
+            start,
             rows,
             cols,
             wrap,
@@ -84,19 +80,9 @@ impl Garden {
     fn pos_to_index(&self, pos: Pos) -> usize {
         let mut pos = pos;
         if self.wrap {
-            if pos.row < 0 {
-                pos.row = pos.row % self.rows + self.rows;
-            }
-            if pos.col < 0 {
-                pos.col = pos.col % self.cols + self.cols;
-            }
-            if pos.row >= self.rows {
-                pos.row %= self.rows;
-            }
-            if pos.col >= self.cols {
-                pos.col %= self.cols;
-            }
-        } else if pos.row < 0 || pos.col < 0 || pos.row >= self.cols || pos.col >= self.cols {
+            pos.row = pos.row.rem_euclid(self.rows);
+            pos.col = pos.col.rem_euclid(self.cols);
+        } else if pos.row < 0 || pos.col < 0 || pos.row >= self.rows || pos.col >= self.cols {
             return usize::MAX;
         }
 
@@ -130,77 +116,151 @@ impl Garden {
         })
     }
 
-    fn iterate(&mut self) {
-        let mut occupied_new: HashSet<Pos> = HashSet::new();
-
-        for pos in self.occupied.iter() {
-            for destination in self.valid_moves(*pos).into_iter().flatten() {
-                occupied_new.insert(destination);
-            }
+    /// Searches outwards from the start one step at a time, stopping after `max_steps`
+    /// or once every reachable plot has been found.
+    fn distance_map(&self, max_steps: usize) -> DistanceMap {
+        if self.wrap && max_steps == usize::MAX && self.repeats_forever() {
+            panic!("Expected a step limit for a garden that repeats forever.");
         }
 
-        self.occupied = occupied_new;
-    }
-
-    fn iterate_many(&mut self, mut count: usize) -> usize {
-        if count % 2 == 1 {
-            self.iterate();
-            count -= 1;
+        // Every step changes `row + col` between odd and even, so a plot first reached
+        // in the next step can only already have been seen in the previous one. Each
+        // frontier is kept sorted so that check is a merge rather than a lookup:
+        let mut previous: Vec<Pos> = Vec::new();
+        let mut frontier: Vec<Pos> = vec![self.start];
+        let mut next: Vec<Pos> = Vec::new();
+        let mut reachable: Vec<usize> = Vec::new();
+
+        while !frontier.is_empty() && reachable.len() <= max_steps {
+            // Plots reached in an odd number of steps can be stepped away from and back to
+            // at every later odd step, and the same for even steps:
+            let earlier = match reachable.len() {
+                0 | 1 => 0,
+                distance => reachable[distance - 2],
+            };
+            reachable.push(earlier + frontier.len());
+
+            next.clear();
+            next.extend(
+                frontier
+                    .iter()
+                    .flat_map(|&pos| self.valid_moves(pos).into_iter().flatten()),
+            );
+            next.sort_unstable();
+            next.dedup();
+
+            let mut earlier = previous.iter().peekable();
+            next.retain(|destination| {
+                while earlier.next_if(|&seen| seen < destination).is_some() {}
+                earlier.peek() != Some(&destination)
+            });
+
+            std::mem::swap(&mut previous, &mut frontier);
+            std::mem::swap(&mut frontier, &mut next);
         }
-        let mut visited = HashSet::with_capacity_and_hasher(count, BuildFnv1Hasher);
-        let mut to_process: Vec<Pos> = self.occupied.iter().cloned().collect();
-        let mut to_process_next: Vec<Pos> = Vec::new();
 
-        let mut count = count as i64;
-        while count >= 0 && (!to_process.is_empty() || !to_process_next.is_empty()) {
-            while let Some(pos) = to_process.pop() {
-                if visited.contains(&pos) {
-                    continue;
-                }
-                visited.insert(pos);
+        DistanceMap {
+            reachable,
+            complete: frontier.is_empty(),
+        }
+    }
 
-                for destination_a in self.valid_moves(pos).into_iter().flatten() {
-                    for destination_b in self.valid_moves(destination_a).into_iter().flatten() {
-                        if destination_b != pos && !visited.contains(&destination_b) {
-                            to_process_next.push(destination_b);
-                        }
+    /// Whether a wrapping garden lets the elf walk to another copy of the start, and so
+    /// on forever. Otherwise the reachable plots all lie in different repeats of the map.
+    fn repeats_forever(&self) -> bool {
+        let mut first_seen: HashMap<usize, Pos> =
+            HashMap::from([(self.pos_to_index(self.start), self.start)]);
+        let mut queue = vec![self.start];
+
+        while let Some(pos) = queue.pop() {
+            for destination in self.valid_moves(pos).into_iter().flatten() {
+                match first_seen.get(&self.pos_to_index(destination)) {
+                    Some(&seen) if seen != destination => return true,
+                    Some(_) => {}
+                    None => {
+                        first_seen.insert(self.pos_to_index(destination), destination);
+                        queue.push(destination);
                     }
                 }
             }
+        }
 
-            count -= 2;
+        false
+    }
+}
+
+/// How many garden plots can be reached after each number of steps, from a single
+/// breadth-first search outwards from the start.
+pub struct DistanceMap {
+    /// Indexed by step count, the plots reachable after exactly that many steps.
+    reachable: Vec<usize>,
+    /// Whether the search found every reachable plot rather than stopping early.
+    complete: bool,
+}
 
-            std::mem::swap(&mut to_process, &mut to_process_next);
+impl DistanceMap {
+    /// The number of plots the elf could be on after exactly `steps` steps.
+    pub fn reachable(&self, steps: usize) -> usize {
+        if let Some(&reachable) = self.reachable.get(steps) {
+            return reachable;
         }
+        assert!(
+            self.complete,
+            "Distance map only covers {} steps, but asked for {}.",
+            self.reachable.len() - 1,
+            steps
+        );
 
-        visited.len()
+        // Every plot has been found, so only the parity of the step count matters:
+        let last = self.reachable.len() - 1;
+        if (steps - last).is_multiple_of(2) {
+            self.reachable[last]
+        } else {
+            self.reachable
+                .get(last.wrapping_sub(1))
+                .copied()
+                .unwrap_or(0)
+        }
     }
+}
 
-    fn score_part_1(&self) -> i64 {
-        self.occupied.len() as i64
-    }
+/// Finds every plot within `max_steps` of the start, with the map repeating forever in
+/// every direction if `wrap` is set. `usize::MAX` searches every plot the start can
+/// reach, which is refused for a wrapping garden unless the start is walled in.
+pub fn day21_distance_map(input: &str, max_steps: usize, wrap: bool) -> DistanceMap {
+    Garden::new(input, wrap).distance_map(max_steps)
 }
 
-pub fn day21_part_1(input: &str) -> i64 {
-    let mut garden = Garden::new(input, false);
-    let goal_steps = if garden.cols == 11 { 6 } else { 64 };
+/// The number of garden plots the elf could be on after exactly `steps` steps, with the
+/// map repeating forever in every direction if `wrap` is set.
+pub fn day21_reachable(input: &str, steps: usize, wrap: bool) -> usize {
+    day21_distance_map(input, steps, wrap).reachable(steps)
+}
 
-    for _ in 0..goal_steps {
-        garden.iterate();
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
     }
-
-    garden.score_part_1()
 }
 
-// Honestly, this part 2 was a bit beyond me. After bashing my head against it for hours
-// I had to look up what others had done. Apparently lagrange interpolation on these
-// 3 points yields the correct result for the input (but not the example). I don't fully
-// understand it all, but at this point I'm happy to collect my stars and be done.
-pub fn day21_part_2(input: &str) -> f64 {
+/// Estimates `day21_reachable` with wrapping for a large number of steps, by fitting a
+/// quadratic to three step counts a whole number of map repeats apart. The map repeats
+/// every `rows` steps vertically and every `cols` steps horizontally, so samples are
+/// spaced by the lowest common multiple of both.
+pub fn day21_extrapolated(input: &str, steps: usize) -> f64 {
+    let garden = Garden::new(input, true);
+    let period = (garden.rows / gcd(garden.rows, garden.cols) * garden.cols) as usize;
+    let offset = steps % period;
+    if steps <= offset + 2 * period {
+        return garden.distance_map(steps).reachable(steps) as f64;
+    }
+
+    let distances = garden.distance_map(offset + 2 * period);
     let mut points: Vec<(f64, f64)> = Vec::new();
-    for count in [65, 131 + 65, 131 * 2 + 65] {
-        let mut garden = Garden::new(input, true);
-        let result = garden.iterate_many(count);
+    for count in [offset, offset + period, offset + 2 * period] {
+        let result = distances.reachable(count);
         points.push((count as f64, result as f64));
     }
 
@@ -210,7 +270,7 @@ pub fn day21_part_2(input: &str) -> f64 {
 
         for j in 0..3 {
             if i != j {
-                term = term * (26501365f64 - points[j].0) / (points[i].0 - points[j].0);
+                term = term * (steps as f64 - points[j].0) / (points[i].0 - points[j].0);
             }
         }
 
@@ -220,9 +280,40 @@ pub fn day21_part_2(input: &str) -> f64 {
     result
 }
 
+pub fn day21_part_1(input: &str) -> i64 {
+    let garden = Garden::new(input, false);
+    let goal_steps = if garden.cols == 11 { 6 } else { 64 };
+
+    garden.distance_map(goal_steps).reachable(goal_steps) as i64
+}
+
+// Honestly, this part 2 was a bit beyond me. After bashing my head against it for hours
+// I had to look up what others had done. Apparently lagrange interpolation on these
+// 3 points yields the correct result for the input (but not the example). I don't fully
+// understand it all, but at this point I'm happy to collect my stars and be done.
+pub fn day21_part_2(input: &str) -> f64 {
+    day21_extrapolated(input, 26501365)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::day21::day21_part_1;
+    use crate::day21::{
+        day21_distance_map, day21_extrapolated, day21_part_1, day21_reachable, Garden,
+    };
+    use std::collections::HashSet;
+
+    /// Counts plots by stepping one tile at a time, tracking every position reached.
+    fn reachable_naive(input: &str, steps: usize, wrap: bool) -> usize {
+        let garden = Garden::new(input, wrap);
+        let mut occupied = HashSet::from([garden.start]);
+        for _ in 0..steps {
+            occupied = occupied
+                .iter()
+                .flat_map(|&pos| garden.valid_moves(pos).into_iter().flatten())
+                .collect();
+        }
+        occupied.len()
+    }
 
     #[test]
     pub fn part1_example() {
@@ -243,4 +334,84 @@ mod tests {
             16
         );
     }
+
+    #[test]
+    pub fn rectangular_gardens() {
+        // Tall, with the start in a corner:
+        let tall = ".S.\n...\n...\n...\n...\n...\n...";
+        assert_eq!(day21_reachable(tall, 4, false), 7);
+        // Wide, with the start off centre:
+        let wide = ".......\n..S....\n.......";
+        assert_eq!(day21_reachable(wide, 3, false), 9);
+
+        // With no rocks, wrapping gardens of any shape reach a full diamond:
+        for input in [tall, wide] {
+            for steps in [5, 10, 17] {
+                assert_eq!(
+                    day21_reachable(input, steps, true),
+                    (steps + 1) * (steps + 1)
+                );
+            }
+            assert_eq!(day21_extrapolated(input, 1000), 1001.0 * 1001.0);
+        }
+        // A corridor that repeats forever can be searched well past its own size:
+        assert_eq!(day21_reachable("#S#\n#.#", 20001, true), 20002);
+
+        let rocky = "...#.\n.#...\n..#S.\n#....\n...#.\n.#...\n.....";
+        for steps in 0..20 {
+            for wrap in [false, true] {
+                assert_eq!(
+                    day21_reachable(rocky, steps, wrap),
+                    reachable_naive(rocky, steps, wrap)
+                );
+            }
+        }
+    }
+
+    #[test]
+    pub fn distance_map_queries() {
+        let example = "...........
+.....###.#.
+.###.##..#.
+..#.#...#..
+....#.#....
+.##..S####.
+.##..#...#.
+.......##..
+.##.#.####.
+.##..##.##.
+...........";
+        let distances = day21_distance_map(example, 100, true);
+        for (steps, expected) in [(6, 16), (10, 50), (50, 1594), (100, 6536)] {
+            assert_eq!(distances.reachable(steps), expected);
+        }
+
+        // Without wrapping the search finishes, after which only parity matters:
+        let distances = day21_distance_map(example, usize::MAX, false);
+        for steps in 0..30 {
+            assert_eq!(
+                distances.reachable(steps),
+                reachable_naive(example, steps, false)
+            );
+        }
+        assert_eq!(distances.reachable(1000), distances.reachable(28));
+        assert_eq!(distances.reachable(1001), distances.reachable(29));
+
+        let closed_in = "###\n#S#\n###";
+        let distances = day21_distance_map(closed_in, usize::MAX, false);
+        assert_eq!(distances.reachable(0), 1);
+        assert_eq!(distances.reachable(1), 0);
+        assert_eq!(distances.reachable(2), 1);
+
+        let distances = day21_distance_map(closed_in, usize::MAX, true);
+        assert_eq!(distances.reachable(0), 1);
+        assert_eq!(distances.reachable(1), 0);
+        assert_eq!(distances.reachable(1000001), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected a step limit for a garden that repeats forever.")]
+    pub fn unbounded_wrapping_search() {
+        day21_distance_map("...\n.S.\n...", usize::MAX, true);
+    }
 }
@@ -0,0 +1,82 @@
+/// Splits the graph with `successors[node]` leaving each node into strongly connected
+/// components using Tarjan's algorithm. Every component comes after all the components
+/// it can reach.
+pub fn strongly_connected_components(successors: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    struct Tarjan<'a> {
+        successors: &'a [Vec<usize>],
+        index: Vec<Option<usize>>,
+        low_link: Vec<usize>,
+        next_index: usize,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    impl<'a> Tarjan<'a> {
+        fn visit(&mut self, node: usize) {
+            self.index[node] = Some(self.next_index);
+            self.low_link[node] = self.next_index;
+            self.next_index += 1;
+            self.stack.push(node);
+            self.on_stack[node] = true;
+
+            for &successor in self.successors[node].iter() {
+                match self.index[successor] {
+                    None => {
+                        self.visit(successor);
+                        self.low_link[node] = self.low_link[node].min(self.low_link[successor]);
+                    }
+                    Some(index) if self.on_stack[successor] => {
+                        self.low_link[node] = self.low_link[node].min(index);
+                    }
+                    Some(_) => {}
+                }
+            }
+
+            if Some(self.low_link[node]) == self.index[node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack[member] = false;
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        successors,
+        index: vec![None; successors.len()],
+        low_link: vec![0; successors.len()],
+        next_index: 0,
+        stack: Vec::new(),
+        on_stack: vec![false; successors.len()],
+        components: Vec::new(),
+    };
+    for node in 0..successors.len() {
+        if tarjan.index[node].is_none() {
+            tarjan.visit(node);
+        }
+    }
+
+    tarjan.components
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::graph::strongly_connected_components;
+
+    #[test]
+    pub fn components() {
+        // 0 -> 1 -> 2 -> 0 loop, then 2 -> 3 -> 4 -> 3 loop, with 5 on its own:
+        let successors = vec![vec![1], vec![2], vec![0, 3], vec![4], vec![3], vec![]];
+        let mut components = strongly_connected_components(&successors);
+        for component in components.iter_mut() {
+            component.sort();
+        }
+        assert_eq!(components, [vec![3, 4], vec![0, 1, 2], vec![5]]);
+    }
+}
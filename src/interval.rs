@@ -0,0 +1,374 @@
+/// The half-open range of integers `start..end`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy, Hash)]
+pub struct Interval {
+    pub start: i64,
+    pub end: i64,
+}
+
+impl Interval {
+    pub fn new(start: i64, end: i64) -> Interval {
+        Interval { start, end }
+    }
+
+    pub fn from_length(start: i64, length: i64) -> Interval {
+        Interval::new(start, start + length)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+
+    pub fn len(&self) -> i64 {
+        (self.end - self.start).max(0)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        self.start <= value && value < self.end
+    }
+
+    /// The overlap of both intervals, which may be empty.
+    pub fn intersection(&self, other: &Interval) -> Interval {
+        Interval::new(self.start.max(other.start), self.end.min(other.end))
+    }
+
+    /// The parts of this interval before and after `other`. Either may be empty.
+    pub fn difference(&self, other: &Interval) -> (Interval, Interval) {
+        (
+            Interval::new(self.start, self.end.min(other.start)),
+            Interval::new(self.start.max(other.end), self.end),
+        )
+    }
+
+    pub fn shift(&self, offset: i64) -> Interval {
+        Interval::new(self.start + offset, self.end + offset)
+    }
+}
+
+/// A set of integers stored as sorted intervals. Intervals are never empty, never
+/// overlap and never touch, so every set has exactly one representation.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct IntervalSet {
+    intervals: Vec<Interval>,
+}
+
+impl IntervalSet {
+    pub fn new() -> IntervalSet {
+        IntervalSet::default()
+    }
+
+    pub fn intervals(&self) -> &[Interval] {
+        &self.intervals
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.intervals.is_empty()
+    }
+
+    /// The number of integers in the set.
+    pub fn len(&self) -> i64 {
+        self.intervals.iter().map(Interval::len).sum()
+    }
+
+    pub fn min(&self) -> Option<i64> {
+        self.intervals.first().map(|interval| interval.start)
+    }
+
+    pub fn contains(&self, value: i64) -> bool {
+        let index = self
+            .intervals
+            .partition_point(|interval| interval.end <= value);
+        self.intervals
+            .get(index)
+            .is_some_and(|interval| interval.contains(value))
+    }
+
+    pub fn union(&self, other: &IntervalSet) -> IntervalSet {
+        self.intervals
+            .iter()
+            .chain(other.intervals.iter())
+            .copied()
+            .collect()
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut result = Vec::new();
+        let (mut i, mut j) = (0, 0);
+
+        while i < self.intervals.len() && j < other.intervals.len() {
+            let (a, b) = (self.intervals[i], other.intervals[j]);
+            let overlap = a.intersection(&b);
+            if !overlap.is_empty() {
+                result.push(overlap);
+            }
+            if a.end < b.end {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        IntervalSet { intervals: result }
+    }
+
+    pub fn difference(&self, other: &IntervalSet) -> IntervalSet {
+        let mut result = Vec::new();
+        let mut j = 0;
+
+        for &interval in self.intervals.iter() {
+            let mut remaining = interval;
+            while j < other.intervals.len() && other.intervals[j].end <= remaining.start {
+                j += 1;
+            }
+            let mut k = j;
+            while k < other.intervals.len() && other.intervals[k].start < remaining.end {
+                let (before, after) = remaining.difference(&other.intervals[k]);
+                if !before.is_empty() {
+                    result.push(before);
+                }
+                remaining = after;
+                k += 1;
+            }
+            if !remaining.is_empty() {
+                result.push(remaining);
+            }
+        }
+
+        IntervalSet { intervals: result }
+    }
+}
+
+impl FromIterator<Interval> for IntervalSet {
+    /// Sorts the intervals, dropping empty ones and merging any that overlap or touch.
+    fn from_iter<I: IntoIterator<Item = Interval>>(iter: I) -> IntervalSet {
+        let mut intervals: Vec<Interval> = iter
+            .into_iter()
+            .filter(|interval| !interval.is_empty())
+            .collect();
+        intervals.sort();
+
+        let mut merged: Vec<Interval> = Vec::with_capacity(intervals.len());
+        for interval in intervals {
+            match merged.last_mut() {
+                Some(last) if interval.start <= last.end => last.end = last.end.max(interval.end),
+                _ => merged.push(interval),
+            }
+        }
+
+        IntervalSet { intervals: merged }
+    }
+}
+
+/// A function that adds a fixed offset to every value inside each of its segments and
+/// leaves values outside them unchanged. Segments are sorted, never overlap and never
+/// have an offset of zero.
+#[derive(PartialEq, Eq, Debug, Clone, Default)]
+pub struct PiecewiseMap {
+    segments: Vec<(Interval, i64)>,
+}
+
+impl PiecewiseMap {
+    /// The identity function.
+    pub fn new() -> PiecewiseMap {
+        PiecewiseMap::default()
+    }
+
+    /// Builds a map from possibly overlapping segments, where earlier segments take
+    /// priority over later ones.
+    pub fn from_segments(segments: impl IntoIterator<Item = (Interval, i64)>) -> PiecewiseMap {
+        let mut covered = IntervalSet::new();
+        let mut result = Vec::new();
+
+        for (interval, offset) in segments {
+            let interval: IntervalSet = std::iter::once(interval).collect();
+            for &piece in interval.difference(&covered).intervals() {
+                result.push((piece, offset));
+            }
+            covered = covered.union(&interval);
+        }
+
+        PiecewiseMap::normalised(result)
+    }
+
+    /// Sorts disjoint segments, dropping those that change nothing and merging those
+    /// that touch and share an offset.
+    fn normalised(mut segments: Vec<(Interval, i64)>) -> PiecewiseMap {
+        segments.retain(|(interval, offset)| !interval.is_empty() && *offset != 0);
+        segments.sort();
+
+        let mut merged: Vec<(Interval, i64)> = Vec::with_capacity(segments.len());
+        for (interval, offset) in segments {
+            match merged.last_mut() {
+                Some((last, last_offset))
+                    if last.end == interval.start && *last_offset == offset =>
+                {
+                    last.end = interval.end
+                }
+                _ => merged.push((interval, offset)),
+            }
+        }
+
+        PiecewiseMap { segments: merged }
+    }
+
+    pub fn segments(&self) -> &[(Interval, i64)] {
+        &self.segments
+    }
+
+    pub fn apply(&self, value: i64) -> i64 {
+        let index = self
+            .segments
+            .partition_point(|(interval, _)| interval.end <= value);
+        match self.segments.get(index) {
+            Some((interval, offset)) if interval.contains(value) => value + offset,
+            _ => value,
+        }
+    }
+
+    /// Splits `interval` at the segment boundaries, pairing each piece with the offset
+    /// applied to it. Pieces outside every segment have an offset of zero.
+    pub fn pieces(&self, interval: Interval) -> Vec<(Interval, i64)> {
+        let mut result = Vec::new();
+        let mut start = interval.start;
+        let first = self
+            .segments
+            .partition_point(|(segment, _)| segment.end <= interval.start);
+
+        for &(segment, offset) in self.segments[first..].iter() {
+            if segment.start >= interval.end {
+                break;
+            }
+            if start < segment.start {
+                result.push((Interval::new(start, segment.start), 0));
+            }
+            result.push((segment.intersection(&interval), offset));
+            start = segment.end;
+        }
+        if start < interval.end {
+            result.push((Interval::new(start, interval.end), 0));
+        }
+
+        result
+    }
+
+    /// Every value the map sends the values in `set` to.
+    pub fn image(&self, set: &IntervalSet) -> IntervalSet {
+        set.intervals()
+            .iter()
+            .flat_map(|&interval| self.pieces(interval))
+            .map(|(piece, offset)| piece.shift(offset))
+            .collect()
+    }
+
+    /// Every value the map sends into `set`. The map need not be one-to-one, so this may
+    /// be larger or smaller than `set`.
+    pub fn preimage(&self, set: &IntervalSet) -> IntervalSet {
+        self.pieces(Interval::new(i64::MIN, i64::MAX))
+            .into_iter()
+            .flat_map(|(piece, offset)| {
+                let image: IntervalSet = std::iter::once(piece.shift(offset)).collect();
+                image
+                    .intersection(set)
+                    .intervals()
+                    .iter()
+                    .map(|interval| interval.shift(-offset))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// The map that applies this one and then `next`.
+    pub fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut result = Vec::new();
+
+        for (piece, offset) in self.pieces(Interval::new(i64::MIN, i64::MAX)) {
+            for (image, next_offset) in next.pieces(piece.shift(offset)) {
+                result.push((image.shift(-offset), offset + next_offset));
+            }
+        }
+
+        PiecewiseMap::normalised(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interval::{Interval, IntervalSet, PiecewiseMap};
+
+    fn set(intervals: &[(i64, i64)]) -> IntervalSet {
+        intervals
+            .iter()
+            .map(|&(start, end)| Interval::new(start, end))
+            .collect()
+    }
+
+    #[test]
+    pub fn interval_set_algebra() {
+        let a = set(&[(5, 10), (0, 3), (3, 4), (20, 20)]);
+        assert_eq!(a, set(&[(0, 4), (5, 10)]));
+        assert_eq!(a.len(), 9);
+        assert_eq!(a.min(), Some(0));
+        assert!(a.contains(3) && !a.contains(4) && a.contains(9) && !a.contains(10));
+
+        let b = set(&[(2, 6), (8, 12)]);
+        assert_eq!(a.union(&b), set(&[(0, 12)]));
+        assert_eq!(a.intersection(&b), set(&[(2, 4), (5, 6), (8, 10)]));
+        assert_eq!(a.difference(&b), set(&[(0, 2), (6, 8)]));
+        assert_eq!(b.difference(&a), set(&[(4, 5), (10, 12)]));
+        assert!(a.difference(&a).is_empty());
+    }
+
+    #[test]
+    pub fn piecewise_map() {
+        // Earlier segments win where they overlap later ones:
+        let map = PiecewiseMap::from_segments([
+            (Interval::new(10, 20), 100),
+            (Interval::new(15, 30), -5),
+            (Interval::new(30, 40), -5),
+        ]);
+        assert_eq!(
+            map.segments(),
+            [(Interval::new(10, 20), 100), (Interval::new(20, 40), -5)]
+        );
+        assert_eq!(map.apply(9), 9);
+        assert_eq!(map.apply(10), 110);
+        assert_eq!(map.apply(39), 34);
+        assert_eq!(map.apply(40), 40);
+
+        assert_eq!(
+            map.image(&set(&[(0, 25)])),
+            set(&[(0, 10), (15, 20), (110, 120)])
+        );
+
+        // Values in 10..20 are moved away, so only 20..25 reaches 15..20:
+        assert_eq!(
+            map.preimage(&set(&[(15, 20), (35, 45)])),
+            set(&[(20, 25), (40, 45)])
+        );
+        assert!(map.preimage(&set(&[(110, 115)])).contains(12));
+    }
+
+    #[test]
+    pub fn composition() {
+        let first = PiecewiseMap::from_segments([(Interval::new(0, 10), 10)]);
+        let second =
+            PiecewiseMap::from_segments([(Interval::new(5, 15), -5), (Interval::new(15, 20), -10)]);
+        let composed = first.then(&second);
+
+        for value in -5..30 {
+            assert_eq!(composed.apply(value), second.apply(first.apply(value)));
+        }
+        // 5..10 is sent to 15..20 and straight back again:
+        assert_eq!(
+            composed.segments(),
+            [
+                (Interval::new(0, 5), 5),
+                (Interval::new(10, 15), -5),
+                (Interval::new(15, 20), -10),
+            ]
+        );
+
+        let identity = PiecewiseMap::new();
+        assert_eq!(first.then(&identity), first);
+        assert_eq!(identity.then(&first), first);
+    }
+}
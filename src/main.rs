@@ -16,8 +16,10 @@ use day08::day08_part_1;
 use day08::day08_part_2;
 use day09::day09_part_1;
 use day09::day09_part_2;
+use day10::day10_enclosed;
 use day10::day10_part_1;
 use day10::day10_part_2;
+use day10::EnclosedAlgorithm;
 use day11::day11_part_1;
 use day11::day11_part_2;
 use day12::day12_part_1;
@@ -48,6 +50,8 @@ use day24::day24_part_1;
 use day24::day24_part_2;
 use day25::day25_part_1;
 
+pub mod bucket_map;
+pub mod cycle;
 pub mod day01;
 pub mod day02;
 pub mod day03;
@@ -74,6 +78,8 @@ pub mod day23;
 pub mod day24;
 pub mod day25;
 pub mod fnv1;
+pub mod graph;
+pub mod interval;
 
 fn read(path: &str) -> String {
     std::fs::read_to_string(path)
@@ -87,6 +93,18 @@ fn execute<T: std::fmt::Display + std::fmt::Debug + PartialEq>(
     part: i8,
     func: fn(&str) -> T,
     expected: Option<T>,
+) {
+    execute_labelled(day, part, "", func, expected);
+}
+
+/// Like `execute`, but with `label` after the row to tell apart several ways of solving
+/// the same part.
+fn execute_labelled<T: std::fmt::Display + std::fmt::Debug + PartialEq>(
+    day: i8,
+    part: i8,
+    label: &str,
+    func: fn(&str) -> T,
+    expected: Option<T>,
 ) {
     let input = read(&format!("./src/day{:0>2}.txt", day));
     let start = std::time::Instant::now();
@@ -102,10 +120,15 @@ fn execute<T: std::fmt::Display + std::fmt::Debug + PartialEq>(
         format!("{:.2}s", elapsed.as_secs_f64())
     };
 
-    println!(
+    let row = format!(
         "| Day {:>2} | Part {} | {:>16} | {:>8} |",
         day, part, result, elapsed_str
     );
+    if label.is_empty() {
+        println!("{}", row);
+    } else {
+        println!("{} {}", row, label);
+    }
     match expected {
         None => {}
         Some(expected) => {
@@ -135,7 +158,21 @@ fn main() {
     execute(9, 1, day09_part_1, Some(1696140818));
     execute(9, 2, day09_part_2, Some(1152));
     execute(10, 1, day10_part_1, Some(6806));
-    execute(10, 2, day10_part_2, Some(449));
+    execute_labelled(10, 2, "flood fill", day10_part_2, Some(449));
+    execute_labelled(
+        10,
+        2,
+        "shoelace",
+        |input| day10_enclosed(input, EnclosedAlgorithm::Shoelace),
+        Some(449),
+    );
+    execute_labelled(
+        10,
+        2,
+        "scanline",
+        |input| day10_enclosed(input, EnclosedAlgorithm::Scanline),
+        Some(449),
+    );
     execute(11, 1, day11_part_1, Some(9214785));
     execute(11, 2, day11_part_2, Some(613686987427));
     execute(12, 1, day12_part_1, Some(6935));